@@ -1,5 +1,10 @@
 ﻿use crate::Dim;
-use std::collections::HashMap;
+use graph::{Encode, Encoder};
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::{Hash, Hasher},
+};
 
 /// 神经网络标量参数
 #[derive(Clone, Debug)]
@@ -73,6 +78,26 @@ impl Arg {
         }
     }
 
+    /// 代入部分变量的值，未绑定的变量保留为符号。
+    ///
+    /// 若代入后某个维度的相等约束不可能成立，返回 `None`。
+    pub fn partial_substitute(self, value: &HashMap<&str, usize>) -> Option<Self> {
+        Some(match self {
+            Self::Dim(dim) => Self::Dim(dim.partial_substitute(value)?),
+            Self::Arr(args) => Self::Arr(
+                args.into_iter()
+                    .map(|a| a.partial_substitute(value))
+                    .collect::<Option<_>>()?,
+            ),
+            Self::Dict(map) => Self::Dict(
+                map.into_iter()
+                    .map(|(k, v)| v.partial_substitute(value).map(|v| (k, v)))
+                    .collect::<Option<_>>()?,
+            ),
+            primitive => primitive,
+        })
+    }
+
     pub fn to_usize(&self) -> usize {
         match self {
             Self::Dim(dim) => dim.to_usize(),
@@ -81,3 +106,118 @@ impl Arg {
         }
     }
 }
+
+/// 浮点数按位比较，使相等关系与哈希一致。
+impl PartialEq for Arg {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Dim(a), Self::Dim(b)) => a == b,
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::Int(a), Self::Int(b)) => a == b,
+            (Self::Float(a), Self::Float(b)) => a.to_bits() == b.to_bits(),
+            (Self::Str(a), Self::Str(b)) => a == b,
+            (Self::Arr(a), Self::Arr(b)) => a == b,
+            (Self::Dict(a), Self::Dict(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Arg {}
+
+impl Hash for Arg {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Self::Dim(dim) => dim.hash(state),
+            Self::Bool(val) => val.hash(state),
+            Self::Int(val) => val.hash(state),
+            Self::Float(val) => val.to_bits().hash(state),
+            Self::Str(val) => val.hash(state),
+            Self::Arr(args) => args.hash(state),
+            Self::Dict(map) => {
+                // 按键排序，使哈希与插入顺序无关
+                let mut entries = map.iter().collect::<Vec<_>>();
+                entries.sort_unstable_by_key(|(k, _)| *k);
+                entries.hash(state)
+            }
+        }
+    }
+}
+
+/// 变体按固定的标签编码，字典按键排序
+impl Encode for Arg {
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            Self::Dim(dim) => {
+                0u8.encode(enc);
+                dim.encode(enc)
+            }
+            Self::Bool(val) => {
+                1u8.encode(enc);
+                val.encode(enc)
+            }
+            Self::Int(val) => {
+                2u8.encode(enc);
+                val.encode(enc)
+            }
+            Self::Float(val) => {
+                3u8.encode(enc);
+                val.encode(enc)
+            }
+            Self::Str(val) => {
+                4u8.encode(enc);
+                val.encode(enc)
+            }
+            Self::Arr(args) => {
+                5u8.encode(enc);
+                args.encode(enc)
+            }
+            Self::Dict(map) => {
+                6u8.encode(enc);
+                let mut entries = map.iter().collect::<Vec<_>>();
+                entries.sort_unstable_by_key(|(k, _)| *k);
+                entries.len().encode(enc);
+                for (k, v) in entries {
+                    k.encode(enc);
+                    v.encode(enc)
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dim(dim) => write!(f, "{dim}"),
+            Self::Bool(val) => write!(f, "{val}"),
+            Self::Int(val) => write!(f, "{val}"),
+            Self::Float(val) => write!(f, "{val:?}"),
+            Self::Str(val) => write!(f, "{val:?}"),
+            Self::Arr(args) => {
+                write!(f, "[")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?
+                    }
+                    write!(f, "{arg}")?
+                }
+                write!(f, "]")
+            }
+            Self::Dict(map) => {
+                // 按键排序，保证输出稳定
+                let mut entries = map.iter().collect::<Vec<_>>();
+                entries.sort_unstable_by_key(|(k, _)| *k);
+                write!(f, "{{")?;
+                for (i, (k, v)) in entries.into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?
+                    }
+                    write!(f, "{k}: {v}")?
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
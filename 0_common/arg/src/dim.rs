@@ -2,8 +2,12 @@
 //!
 //! 考虑到形状运算的实际情况，只支持多项式的运算。
 
-use std::collections::{BTreeSet, HashMap};
-use symbolic_expr::Expr;
+use crate::poly::Poly;
+use graph::{Encode, Encoder};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+};
 
 /// 形状的一个维度，或参与维度运算的值。
 ///
@@ -16,42 +20,141 @@ use symbolic_expr::Expr;
 /// let expr = (a + _1 - 2) * 3 / (b + 1);
 /// assert_eq!(expr.substitute(&HashMap::from([("a", 8), ("b", 6)])), Some(3));
 /// ```
+///
+/// 表达式保存为规范形式，两个 `Dim` 相等当且仅当表达式恒等并且携带相同的相等约束。
+/// 检查形状时用 [`Dim::expr_eq`] 只比较表达式：
+///
+/// ```rust
+/// # use arg::{Dim, make_eq};
+/// let n = Dim::from("n");
+/// let m = Dim::from("m");
+/// assert_eq!((n.clone() + 1) * 2 - 2, n.clone() * 2);
+/// assert_eq!((n.clone() * 2 + 3) / 2, n.clone() + 1);
+/// assert_eq!(n.clone() / 2 / 3, n.clone() / 6);
+/// let eq = make_eq(&[&n, &m]).unwrap() + 1;
+/// assert_ne!(eq, m.clone() + 1);
+/// assert!(eq.expr_eq(&(m + 1)));
+/// ```
 #[derive(Clone, Debug)]
 pub struct Dim {
-    expr: Expr,
-    eq_constraints: Vec<Expr>,
+    expr: Poly,
+    eq_constraints: BTreeSet<Poly>,
+}
+
+impl PartialEq for Dim {
+    fn eq(&self, other: &Self) -> bool {
+        self.expr == other.expr && self.eq_constraints == other.eq_constraints
+    }
+}
+
+impl Eq for Dim {}
+
+impl std::hash::Hash for Dim {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.expr.hash(state);
+        self.eq_constraints.hash(state)
+    }
+}
+
+/// 编码规范形式的表达式和有序的约束集合
+impl Encode for Dim {
+    fn encode(&self, enc: &mut Encoder) {
+        self.expr.encode(enc);
+        self.eq_constraints.len().encode(enc);
+        for constraint in &self.eq_constraints {
+            constraint.encode(enc)
+        }
+    }
 }
 
 impl Dim {
+    /// 表达式恒等，不比较携带的相等约束。
+    ///
+    /// 用于检查形状，需要保留约束时用 [`make_eq`] 合并。
+    pub fn expr_eq(&self, other: &Self) -> bool {
+        self.expr == other.expr
+    }
+
     /// 统计表达式中出现的变量名。
     pub fn append_variables<'s>(&'s self, set: &mut BTreeSet<&'s str>) {
         self.expr.append_variables(set);
     }
 
+    /// 代入所有变量的值。
+    ///
+    /// 存在未绑定的变量或相等约束不满足时返回 `None`。
     pub fn substitute(&self, value: &HashMap<&str, usize>) -> Option<usize> {
         if self
             .eq_constraints
             .iter()
-            .any(|constraint| constraint.substitute(value) != 0)
+            .any(|constraint| constraint.eval(value) != Some(0))
         {
             None
         } else {
-            Some(self.expr.substitute(value))
+            self.expr.eval(value)?.try_into().ok()
         }
     }
 
+    /// 代入部分变量的值，返回化简后的 `Dim`。
+    ///
+    /// 未绑定的变量保留为符号，已经满足的相等约束被移除。
+    /// 若代入后某个相等约束不可能成立，返回 `None`。
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use arg::Dim;
+    /// let n_tok = Dim::from("n_tok");
+    /// let n_out = Dim::from("n_out");
+    /// let expr = (n_out + 1) * n_tok.clone() + n_tok * 2 - 1;
+    /// let expr = expr.partial_substitute(&HashMap::from([("n_out", 1)])).unwrap();
+    /// assert_eq!(expr.to_string(), "4*n_tok - 1");
+    /// ```
+    pub fn partial_substitute(&self, value: &HashMap<&str, usize>) -> Option<Self> {
+        let mut eq_constraints = BTreeSet::new();
+        for constraint in &self.eq_constraints {
+            let constraint = constraint.partial_substitute(value);
+            match constraint.as_constant() {
+                Some(0) => {}
+                Some(_) => return None,
+                None => {
+                    eq_constraints.insert(constraint.normalize_constraint());
+                }
+            }
+        }
+        Some(Self {
+            expr: self.expr.partial_substitute(value),
+            eq_constraints,
+        })
+    }
+
+    /// 保持表达式不变，额外携带 `other` 的相等约束。
+    ///
+    /// 用于把不出现在输出形状中的维度上建立的约束传递给输出。
+    pub fn with_constraints(mut self, other: &Self) -> Self {
+        self.eq_constraints
+            .extend(other.eq_constraints.iter().cloned());
+        self
+    }
+
     pub fn to_usize(&self) -> usize {
-        match self.expr {
-            Expr::Constant(c) => c,
-            _ => panic!("Dim is not a constant"),
+        match self.expr.as_constant() {
+            Some(c) => usize::try_from(c).unwrap_or_else(|_| panic!("Dim {c} is negative")),
+            None => panic!("Dim is not a constant"),
         }
     }
 }
 
+impl fmt::Display for Dim {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expr)
+    }
+}
+
 /// 从多个 `Dim` 引用创建一个带有相等约束的新 `Dim`。
 ///
 /// 此函数接收多个应该相等的 `Dim` 表达式，并生成一个新的带有相等约束的 `Dim`。
-/// 返回的 `Dim` 将继承第一个输入的表达式，并添加确保所有输入相等的约束。
+/// 返回的 `Dim` 取输入中规范序最小的表达式，并添加确保所有输入相等的约束，
+/// 因此结果与输入的顺序无关。
 ///
 /// # 参数
 ///
@@ -59,7 +162,7 @@ impl Dim {
 ///
 /// # 返回值
 ///
-/// * `Some(Dim)` - 如果表达式可以相等；如果表达式恒等，返回一个不带约束的`Dim`，否则，返回一个带有相等约束的新 `Dim`，相等约束会在substitute时被计算
+/// * `Some(Dim)` - 如果表达式可以相等；如果表达式恒等，返回一个不带新约束的`Dim`，否则，返回一个带有相等约束的新 `Dim`，相等约束会在substitute时被计算。所有输入已携带的约束都会被继承
 /// * `None` - 如果表达式被判定为永远不相等
 ///
 /// # Panic
@@ -67,30 +170,26 @@ impl Dim {
 /// 当 `dims` 长度小于 2 时会发生 panic
 pub fn make_eq(dims: &[&Dim]) -> Option<Dim> {
     assert!(dims.len() > 1);
-    let mut dim = dims[0].clone();
-    for other in dims[1..].iter() {
-        let eq = dim.expr.equivalent(&other.expr);
-        match eq {
-            Some(true) => continue,
-            Some(false) => return None,
-            None => {
-                dim.eq_constraints
-                    .push(dim.expr.clone() - other.expr.clone());
-            }
+    for (i, a) in dims.iter().enumerate() {
+        if dims[i + 1..]
+            .iter()
+            .any(|b| a.expr.equivalent(&b.expr) == Some(false))
+        {
+            return None;
         }
     }
-    Some(dim)
-}
-
-impl PartialEq for Dim {
-    fn eq(&self, other: &Self) -> bool {
-        self.expr == other.expr
-    }
-
-    #[allow(clippy::partialeq_ne_impl)]
-    fn ne(&self, other: &Self) -> bool {
-        self.expr != other.expr
+    let expr = dims.iter().map(|d| &d.expr).min().unwrap().clone();
+    let mut eq_constraints = BTreeSet::new();
+    for dim in dims {
+        if expr.equivalent(&dim.expr).is_none() {
+            eq_constraints.insert((expr.clone() - dim.expr.clone()).normalize_constraint());
+        }
+        eq_constraints.extend(dim.eq_constraints.iter().cloned())
     }
+    Some(Dim {
+        expr,
+        eq_constraints,
+    })
 }
 
 macro_rules! impl_ {
@@ -99,7 +198,7 @@ macro_rules! impl_ {
             fn from(value: $ty) -> Self {
                 Self {
                     expr: value.into(),
-                    eq_constraints: Vec::new(),
+                    eq_constraints: BTreeSet::new(),
                 }
             }
         }
@@ -109,9 +208,12 @@ macro_rules! impl_ {
         impl std::ops::$trait for Dim {
             type Output = Self;
             fn $fn(self, rhs: Self) -> Self::Output {
+                // 运算结果继承两个操作数的约束
+                let mut eq_constraints = self.eq_constraints;
+                eq_constraints.extend(rhs.eq_constraints);
                 Self {
                     expr: self.expr.$fn(rhs.expr),
-                    eq_constraints: Vec::new(),
+                    eq_constraints,
                 }
             }
         }
@@ -138,3 +240,32 @@ impl_!(num-op: Add, add);
 impl_!(num-op: Sub, sub);
 impl_!(num-op: Mul, mul);
 impl_!(num-op: Div, div);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::HashSet, hash::BuildHasher};
+
+    #[test]
+    fn constraints_in_eq() {
+        let n = Dim::from("n");
+        let a = make_eq(&[&n, &Dim::from("m")]).unwrap();
+        let b = make_eq(&[&n, &Dim::from("k")]).unwrap();
+        // 表达式都是 `k`、`m` 和 `n` 中规范序最小的，约束不同
+        let a = a.clone() - a + n.clone();
+        let b = b.clone() - b + n.clone();
+        assert!(a.expr_eq(&b) && a.expr_eq(&n));
+        assert_ne!(a, b);
+        assert_ne!(a, n);
+
+        let hasher = std::hash::RandomState::new();
+        assert_ne!(hasher.hash_one(&a), hasher.hash_one(&b));
+        assert_eq!(HashSet::from([a.clone(), b, n.clone()]).len(), 3);
+
+        // 约束与来源的顺序无关
+        let c = make_eq(&[&Dim::from("m"), &n]).unwrap();
+        let c = c.clone() - c + n;
+        assert_eq!(a, c);
+        assert_eq!(hasher.hash_one(&a), hasher.hash_one(&c))
+    }
+}
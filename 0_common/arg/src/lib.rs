@@ -1,5 +1,6 @@
 mod arg;
 mod dim;
+mod poly;
 
 pub use arg::Arg;
 pub use dim::{Dim, make_eq};
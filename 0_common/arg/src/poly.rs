@@ -0,0 +1,563 @@
+//! 形状多项式的内部表示。
+//!
+//! 多项式以单项式到非零系数的有序映射保存，同类项在构造时合并，
+//! 因此结构相同即表示相同，可以直接比较和哈希。
+//! 无法整除的除法保留为向下取整的商，作为单项式中的一个因子参与运算。
+//!
+//! 原先使用的 `symbolic-expr` 以表达式树保存，没有规范形式，
+//! 不能部分代入、按多项式打印或与哈希一致地比较，因此改为这里的表示。
+
+use graph::{Encode, Encoder};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+};
+
+/// 整系数多项式。
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub(crate) struct Poly(BTreeMap<Monomial, i64>);
+
+/// 单项式，因子到指数的映射，空映射表示常数项。
+type Monomial = BTreeMap<Atom, u32>;
+
+/// 单项式中不可再分的因子。
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+enum Atom {
+    /// 形状变量。
+    Var(String),
+    /// 无法整除的向下取整除法。
+    Div(Box<Poly>, Box<Poly>),
+}
+
+impl Poly {
+    pub fn constant(value: i64) -> Self {
+        let mut ans = Self::default();
+        ans.add_term(Monomial::new(), value);
+        ans
+    }
+
+    fn atom(atom: Atom) -> Self {
+        Self(BTreeMap::from([(Monomial::from([(atom, 1)]), 1)]))
+    }
+
+    /// 若多项式是常数，返回常数值。
+    pub fn as_constant(&self) -> Option<i64> {
+        match self.0.len() {
+            0 => Some(0),
+            1 => self.0.get(&Monomial::new()).copied(),
+            _ => None,
+        }
+    }
+
+    /// 若多项式恰为一个一次因子，返回该因子。
+    fn as_atom(&self) -> Option<&Atom> {
+        match *self.0.iter().collect::<Vec<_>>() {
+            [(mono, 1)] => match *mono.iter().collect::<Vec<_>>() {
+                [(atom, 1)] => Some(atom),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// 判断两个多项式是否相等：
+    /// 恒等时返回 `Some(true)`，差为非零常数时返回 `Some(false)`，否则无法判定。
+    pub fn equivalent(&self, other: &Self) -> Option<bool> {
+        match (self.clone() - other.clone()).as_constant() {
+            Some(0) => Some(true),
+            Some(_) => Some(false),
+            None => None,
+        }
+    }
+
+    /// 统计多项式中出现的变量名。
+    pub fn append_variables<'s>(&'s self, set: &mut BTreeSet<&'s str>) {
+        for atom in self.0.keys().flat_map(|mono| mono.keys()) {
+            match atom {
+                Atom::Var(name) => {
+                    set.insert(name);
+                }
+                Atom::Div(num, den) => {
+                    num.append_variables(set);
+                    den.append_variables(set)
+                }
+            }
+        }
+    }
+
+    /// 代入所有变量求值，存在未绑定的变量时返回 `None`。
+    pub fn eval(&self, value: &HashMap<&str, usize>) -> Option<i64> {
+        self.0.iter().try_fold(0, |acc, (mono, &coef)| {
+            let term = mono.iter().try_fold(coef, |acc, (atom, &exp)| {
+                Some(acc * atom.eval(value)?.pow(exp))
+            })?;
+            Some(acc + term)
+        })
+    }
+
+    /// 代入部分变量，返回化简后的多项式。
+    pub fn partial_substitute(&self, value: &HashMap<&str, usize>) -> Self {
+        self.0.iter().fold(Self::default(), |acc, (mono, &coef)| {
+            let term = mono.iter().fold(Self::constant(coef), |acc, (atom, &exp)| {
+                let atom = atom.partial_substitute(value);
+                (0..exp).fold(acc, |acc, _| acc * atom.clone())
+            });
+            acc + term
+        })
+    }
+
+    fn add_term(&mut self, mono: Monomial, coef: i64) {
+        use std::collections::btree_map::Entry::{Occupied, Vacant};
+        if coef == 0 {
+            return;
+        }
+        match self.0.entry(mono) {
+            Vacant(entry) => {
+                entry.insert(coef);
+            }
+            Occupied(mut entry) => {
+                *entry.get_mut() += coef;
+                if *entry.get() == 0 {
+                    entry.remove();
+                }
+            }
+        }
+    }
+
+    /// 所有系数的最大公因数。
+    fn content(&self) -> i64 {
+        self.0.values().fold(0, |acc, &c| gcd(acc, c.abs()))
+    }
+
+    /// 将 `self = 0` 形式的约束化为规范形式：约去系数的公因数，并使首项系数为正。
+    pub fn normalize_constraint(self) -> Self {
+        let g = self.content();
+        let g = match self.0.values().next_back() {
+            Some(&c) if c < 0 => -g,
+            Some(_) => g,
+            None => return self,
+        };
+        Self(self.0.into_iter().map(|(m, c)| (m, c / g)).collect())
+    }
+
+    /// 若 `rhs` 是单项式且整除每一项，返回商。
+    fn exact_div(&self, rhs: &Self) -> Option<Self> {
+        let [(den, &c_den)] = *rhs.0.iter().collect::<Vec<_>>() else {
+            return None;
+        };
+        let mut ans = Self::default();
+        for (mono, &coef) in &self.0 {
+            if coef % c_den != 0 {
+                return None;
+            }
+            let mut mono = mono.clone();
+            for (atom, &exp) in den {
+                match mono.get_mut(atom) {
+                    Some(e) if *e > exp => *e -= exp,
+                    Some(e) if *e == exp => {
+                        mono.remove(atom);
+                    }
+                    _ => return None,
+                }
+            }
+            ans.add_term(mono, coef / c_den)
+        }
+        Some(ans)
+    }
+}
+
+impl Atom {
+    fn eval(&self, value: &HashMap<&str, usize>) -> Option<i64> {
+        match self {
+            Self::Var(name) => value.get(&**name).map(|&v| v as _),
+            Self::Div(num, den) => Some(num.eval(value)?.div_euclid(den.eval(value)?)),
+        }
+    }
+
+    fn partial_substitute(&self, value: &HashMap<&str, usize>) -> Poly {
+        match self {
+            Self::Var(name) => match value.get(&**name) {
+                Some(&v) => Poly::constant(v as _),
+                None => Poly::atom(self.clone()),
+            },
+            Self::Div(num, den) => num.partial_substitute(value) / den.partial_substitute(value),
+        }
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+impl From<usize> for Poly {
+    fn from(value: usize) -> Self {
+        Self::constant(value as _)
+    }
+}
+
+impl From<&str> for Poly {
+    fn from(value: &str) -> Self {
+        Self::atom(Atom::Var(value.into()))
+    }
+}
+
+impl From<String> for Poly {
+    fn from(value: String) -> Self {
+        Self::atom(Atom::Var(value))
+    }
+}
+
+impl std::ops::Add for Poly {
+    type Output = Self;
+    fn add(mut self, rhs: Self) -> Self::Output {
+        for (mono, coef) in rhs.0 {
+            self.add_term(mono, coef)
+        }
+        self
+    }
+}
+
+impl std::ops::Sub for Poly {
+    type Output = Self;
+    fn sub(mut self, rhs: Self) -> Self::Output {
+        for (mono, coef) in rhs.0 {
+            self.add_term(mono, -coef)
+        }
+        self
+    }
+}
+
+impl std::ops::Mul for Poly {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut ans = Self::default();
+        for (a, &ca) in &self.0 {
+            for (b, &cb) in &rhs.0 {
+                let mut mono = a.clone();
+                for (atom, &exp) in b {
+                    *mono.entry(atom.clone()).or_insert(0) += exp
+                }
+                ans.add_term(mono, ca * cb)
+            }
+        }
+        ans
+    }
+}
+
+impl std::ops::Div for Poly {
+    type Output = Self;
+    fn div(self, rhs: Self) -> Self::Output {
+        assert_ne!(rhs.as_constant(), Some(0), "division by zero");
+        if self.0.is_empty() {
+            return self;
+        }
+        if self == rhs {
+            return Self::constant(1);
+        }
+        if let Some(ans) = self.exact_div(&rhs) {
+            return ans;
+        }
+        // 嵌套的向下取整除法合并为一次，floor(floor(a / b) / c) = floor(a / (b*c))
+        if let Some(Atom::Div(num, den)) = self.as_atom() {
+            return *num.clone() / (*den.clone() * rhs);
+        }
+        let num = match rhs.as_constant() {
+            Some(c) => {
+                // 分离出能整除的部分，floor((c*q + r) / c) = q + floor(r / c)
+                let mut q = Self::default();
+                let mut r = Self::default();
+                for (mono, coef) in self.0 {
+                    q.add_term(mono.clone(), coef.div_euclid(c));
+                    r.add_term(mono, coef.rem_euclid(c))
+                }
+                match r.as_constant() {
+                    Some(r) => return q + Self::constant(r.div_euclid(c)),
+                    None if !q.0.is_empty() => return q + r / rhs,
+                    None => r,
+                }
+            }
+            None => self,
+        };
+        // 约去公因数，floor(g*a / g*b) = floor(a / b)
+        let g = gcd(num.content(), rhs.content());
+        let [num, den] = [num, rhs].map(|p| {
+            if g > 1 {
+                Self(p.0.into_iter().map(|(m, c)| (m, c / g)).collect())
+            } else {
+                p
+            }
+        });
+        if den.as_constant() == Some(1) {
+            num
+        } else {
+            Self::atom(Atom::Div(Box::new(num), Box::new(den)))
+        }
+    }
+}
+
+/// 按规范形式中单项式的顺序编码
+impl Encode for Poly {
+    fn encode(&self, enc: &mut Encoder) {
+        self.0.len().encode(enc);
+        for (mono, coef) in &self.0 {
+            mono.len().encode(enc);
+            for (atom, exp) in mono {
+                atom.encode(enc);
+                exp.encode(enc)
+            }
+            coef.encode(enc)
+        }
+    }
+}
+
+impl Encode for Atom {
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            Self::Var(name) => {
+                0u8.encode(enc);
+                name.encode(enc)
+            }
+            Self::Div(num, den) => {
+                1u8.encode(enc);
+                num.encode(enc);
+                den.encode(enc)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Poly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "0");
+        }
+        // 高次项在前，同次项保持字典序
+        let mut terms = self.0.iter().collect::<Vec<_>>();
+        terms.sort_by_key(|(mono, _)| Reverse(mono.values().sum::<u32>()));
+        for (i, (mono, &coef)) in terms.into_iter().enumerate() {
+            match (i, coef < 0) {
+                (0, false) => {}
+                (0, true) => write!(f, "-")?,
+                (_, false) => write!(f, " + ")?,
+                (_, true) => write!(f, " - ")?,
+            }
+            let coef = coef.unsigned_abs();
+            if mono.is_empty() {
+                write!(f, "{coef}")?;
+                continue;
+            }
+            if coef != 1 {
+                write!(f, "{coef}*")?
+            }
+            let alone = coef == 1 && mono.len() == 1;
+            for (j, (atom, &exp)) in mono.iter().enumerate() {
+                if j > 0 {
+                    write!(f, "*")?
+                }
+                match atom {
+                    Atom::Div(..) if !alone || exp > 1 => write!(f, "({atom})")?,
+                    _ => write!(f, "{atom}")?,
+                }
+                if exp > 1 {
+                    write!(f, "^{exp}")?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Var(name) => write!(f, "{name}"),
+            Self::Div(num, den) => {
+                for (i, p) in [num, den].into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "/")?
+                    }
+                    if p.is_factor() {
+                        write!(f, "{p}")?
+                    } else {
+                        write!(f, "({p})")?
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Poly {
+    /// 多项式是否可以不加括号地作为因子打印。
+    fn is_factor(&self) -> bool {
+        match self.0.iter().next() {
+            Some((mono, &coef)) if self.0.len() == 1 => {
+                if mono.is_empty() {
+                    coef > 0
+                } else {
+                    coef == 1 && matches!(*mono.iter().collect::<Vec<_>>(), [(Atom::Var(_), 1)])
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(name: &str) -> Poly {
+        Poly::from(name)
+    }
+
+    fn c(value: i64) -> Poly {
+        Poly::constant(value)
+    }
+
+    #[test]
+    fn canonical() {
+        let [n, m] = [var("n"), var("m")];
+        // 交换律、结合律和分配律得到相同的结构
+        assert_eq!(n.clone() + m.clone(), m.clone() + n.clone());
+        assert_eq!(n.clone() * m.clone(), m.clone() * n.clone());
+        assert_eq!(
+            (n.clone() + c(1)) * (n.clone() - c(1)),
+            n.clone() * n.clone() - c(1)
+        );
+        assert_eq!(
+            (n.clone() + m.clone()) * c(2),
+            n.clone() + m.clone() + m.clone() + n.clone()
+        );
+        // 同类项合并，系数为 0 的项被移除
+        assert_eq!(n.clone() - n.clone(), Poly::default());
+        assert_eq!((n.clone() - n.clone()).as_constant(), Some(0));
+        assert_eq!((n.clone() * c(0) + c(3)).as_constant(), Some(3));
+        assert_eq!(n.as_constant(), None);
+
+        assert_eq!(n.equivalent(&(n.clone() + c(0))), Some(true));
+        assert_eq!(n.equivalent(&(n.clone() + c(1))), Some(false));
+        assert_eq!(n.equivalent(&m), None);
+    }
+
+    #[test]
+    fn normalize_constraint() {
+        let n = var("n");
+        let m = var("m");
+        let a = (c(4) - n.clone() * c(2)).normalize_constraint();
+        let b = (n.clone() * c(3) - c(6)).normalize_constraint();
+        assert_eq!(a, b);
+        assert_eq!(a, n.clone() - c(2));
+        // 约束的两侧交换后规范形式不变
+        assert_eq!(
+            (n.clone() - m.clone()).normalize_constraint(),
+            (m - n).normalize_constraint()
+        );
+        assert_eq!(Poly::default().normalize_constraint(), Poly::default())
+    }
+
+    #[test]
+    fn division() {
+        let [n, m] = [var("n"), var("m")];
+        // 能整除时直接得到商
+        assert_eq!((n.clone() * c(6)) / c(3), n.clone() * c(2));
+        assert_eq!((n.clone() * m.clone() * c(2)) / m.clone(), n.clone() * c(2));
+        assert_eq!(n.clone() / n.clone(), c(1));
+        assert_eq!(Poly::default() / n.clone(), Poly::default());
+        // 分离出能整除的部分
+        assert_eq!((n.clone() * c(2) + c(3)) / c(2), n.clone() + c(1));
+        assert_eq!(
+            (n.clone() * c(4) + m.clone()) / c(2),
+            n.clone() * c(2) + m.clone() / c(2)
+        );
+        // 嵌套的除法合并，公因数约去
+        assert_eq!(n.clone() / c(2) / c(3), n.clone() / c(6));
+        assert_eq!(n.clone() / m.clone() / c(2), n.clone() / (m.clone() * c(2)));
+        assert_eq!(
+            (n.clone() * c(2)) / (m.clone() * c(2)),
+            n.clone() / m.clone()
+        );
+        // 不能整除的除法是一个因子
+        assert_ne!(n.clone() / c(2) * c(2), n);
+        assert_eq!((n.clone() / c(2)).as_constant(), None);
+    }
+
+    #[test]
+    fn division_floor() {
+        let [n, m] = [var("n"), var("m")];
+        let exprs = [
+            (n.clone(), c(2)),
+            (n.clone() - c(3), c(2)),
+            (n.clone() * c(3) + c(1), c(6)),
+            (n.clone() * m.clone() + c(1), m.clone()),
+            (n.clone() + m.clone(), m.clone() + c(1)),
+            (n.clone() / c(2), m.clone()),
+        ];
+        for (num, den) in exprs {
+            let q = num.clone() / den.clone();
+            for n in 0..20 {
+                for m in 1..7 {
+                    let value = HashMap::from([("n", n), ("m", m)]);
+                    let num = num.eval(&value).unwrap();
+                    let den = den.eval(&value).unwrap();
+                    assert_eq!(q.eval(&value), Some(num.div_euclid(den)), "{q} n={n} m={m}")
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "division by zero")]
+    fn division_by_zero() {
+        let _ = var("n") / (var("m") - var("m"));
+    }
+
+    #[test]
+    fn substitution() {
+        let [n, m] = [var("n"), var("m")];
+        let expr = n.clone() * m.clone() + n.clone() / m.clone() + c(1);
+
+        let mut vars = BTreeSet::new();
+        expr.append_variables(&mut vars);
+        assert_eq!(vars, BTreeSet::from(["m", "n"]));
+
+        assert_eq!(expr.eval(&HashMap::from([("n", 7)])), None);
+        assert_eq!(expr.eval(&HashMap::from([("n", 7), ("m", 2)])), Some(18));
+
+        // 部分代入后化简，除法中的变量也被代入
+        let m2 = expr.partial_substitute(&HashMap::from([("m", 2)]));
+        assert_eq!(m2, n.clone() * c(2) + n.clone() / c(2) + c(1));
+        assert_eq!(m2.eval(&HashMap::from([("n", 7)])), Some(18));
+        let all = expr.partial_substitute(&HashMap::from([("n", 7), ("m", 2)]));
+        assert_eq!(all.as_constant(), Some(18));
+        // 不相关的变量不影响结果
+        assert_eq!(expr.partial_substitute(&HashMap::from([("k", 1)])), expr);
+    }
+
+    #[test]
+    fn display() {
+        let [n, m] = [var("n"), var("m")];
+        let cases = [
+            (Poly::default(), "0"),
+            (c(-3), "-3"),
+            (n.clone(), "n"),
+            (c(0) - n.clone(), "-n"),
+            (n.clone() * c(2) - c(1), "2*n - 1"),
+            (
+                n.clone() * n.clone() - n.clone() * m.clone() * c(3),
+                "-3*m*n + n^2",
+            ),
+            (n.clone() / c(2), "n/2"),
+            ((n.clone() + c(1)) / m.clone(), "(n + 1)/m"),
+            (n.clone() / (m.clone() * c(2)), "n/(2*m)"),
+            (n.clone() / c(2) * c(3), "3*(n/2)"),
+            (n.clone() / c(2) * (n.clone() / c(2)), "(n/2)^2"),
+            (n.clone() / c(2) * m.clone(), "m*(n/2)"),
+        ];
+        for (expr, text) in cases {
+            assert_eq!(expr.to_string(), text)
+        }
+    }
+}
@@ -9,8 +9,195 @@ pub struct Graph<N, E> {
     pub edges: Box<[E]>,
 }
 
+impl<N: Encode, E: Encode> Graph<N, E> {
+    /// 计算图的结构指纹，覆盖拓扑以及节点和边的规范编码。
+    ///
+    /// 编码的格式由 [`Encode`] 的实现显式给出，以 FNV-1a 算法累积，
+    /// 不依赖标准库 `Hash` 的实现，与程序的构建和平台无关，可以持久化。
+    pub fn fingerprint(&self) -> u64 {
+        let mut enc = Encoder::default();
+        self.topo.encode(&mut enc);
+        self.nodes.encode(&mut enc);
+        self.edges.encode(&mut enc);
+        enc.finish()
+    }
+}
+
+/// 计算指纹使用的规范编码。
+///
+/// 实现者只应通过 [`Encoder`] 写入确定的字节，相等的值必须产生相同的编码。
+pub trait Encode {
+    fn encode(&self, enc: &mut Encoder);
+}
+
+/// 规范编码的写入端，写入的字节直接以 64 位 FNV-1a 累积。
+pub struct Encoder(u64);
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Encoder {
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+        }
+    }
+
+    /// 整数统一按 64 位小端序写入
+    pub fn u64(&mut self, val: u64) {
+        self.bytes(&val.to_le_bytes())
+    }
+
+    #[inline]
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+macro_rules! impl_int {
+    ($($ty:ty)+) => {
+        $(
+            impl Encode for $ty {
+                fn encode(&self, enc: &mut Encoder) {
+                    enc.u64(*self as _)
+                }
+            }
+        )+
+    };
+}
+
+impl_int!(u8 u32 u64 usize i64);
+
+impl Encode for bool {
+    fn encode(&self, enc: &mut Encoder) {
+        (*self as u8).encode(enc)
+    }
+}
+
+/// 浮点数按位编码
+impl Encode for f64 {
+    fn encode(&self, enc: &mut Encoder) {
+        self.to_bits().encode(enc)
+    }
+}
+
+/// 变长的值先写长度，使相邻的值不会混淆
+impl Encode for str {
+    fn encode(&self, enc: &mut Encoder) {
+        self.len().encode(enc);
+        enc.bytes(self.as_bytes())
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, enc: &mut Encoder) {
+        self.as_str().encode(enc)
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode(&self, enc: &mut Encoder) {
+        (**self).encode(enc)
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, enc: &mut Encoder) {
+        self.len().encode(enc);
+        for item in self {
+            item.encode(enc)
+        }
+    }
+}
+
+impl<T: Encode> Encode for Box<[T]> {
+    fn encode(&self, enc: &mut Encoder) {
+        (**self).encode(enc)
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            Some(val) => {
+                true.encode(enc);
+                val.encode(enc)
+            }
+            None => false.encode(enc),
+        }
+    }
+}
+
+impl<T: Encode> Encode for Named<T> {
+    fn encode(&self, enc: &mut Encoder) {
+        self.name.encode(enc);
+        self.value.encode(enc)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Named<T> {
     pub name: String,
     pub value: T,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a() {
+        let mut enc = Encoder::default();
+        assert_eq!(enc.finish(), 0xcbf2_9ce4_8422_2325);
+        enc.bytes(b"a");
+        assert_eq!(enc.finish(), 0xaf63_dc4c_8601_ec8c)
+    }
+
+    #[test]
+    fn length_prefix() {
+        let fingerprint = |items: &[&str]| {
+            let mut enc = Encoder::default();
+            items.encode(&mut enc);
+            enc.finish()
+        };
+        assert_ne!(fingerprint(&["ab", "c"]), fingerprint(&["a", "bc"]));
+        assert_ne!(fingerprint(&["a"]), fingerprint(&["a", ""]))
+    }
+
+    /// 一个节点的图 `x -> op -> y`
+    fn graph(op: &str) -> Graph<Named<String>, usize> {
+        let topo = unsafe {
+            GraphTopo::from_raw_parts(
+                1,
+                1,
+                [1, 0].into(),
+                [TopoNode {
+                    n_local: 0,
+                    n_inputs: 1,
+                    n_outputs: 1,
+                }]
+                .into(),
+            )
+        };
+        Graph {
+            topo,
+            nodes: [Named {
+                name: "node".into(),
+                value: op.into(),
+            }]
+            .into(),
+            edges: [32, 64].into(),
+        }
+    }
+
+    #[test]
+    fn fingerprint() {
+        assert_eq!(graph("relu").fingerprint(), graph("relu").fingerprint());
+        assert_ne!(graph("relu").fingerprint(), graph("gelu").fingerprint());
+        // 编码格式固定，指纹可以持久化，修改编码时需要同时更新这个值
+        assert_eq!(graph("relu").fingerprint(), 0x6c5e_816d_937c_134e)
+    }
+}
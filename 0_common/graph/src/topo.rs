@@ -1,4 +1,5 @@
-﻿use std::ops::Range;
+﻿use crate::{Encode, Encoder};
+use std::ops::Range;
 
 #[derive(Clone)]
 pub struct GraphTopo {
@@ -8,6 +9,21 @@ pub struct GraphTopo {
     pub(crate) nodes: Box<[TopoNode]>,
 }
 
+impl Encode for GraphTopo {
+    fn encode(&self, enc: &mut Encoder) {
+        let Self {
+            n_inputs,
+            n_outputs,
+            connections,
+            nodes,
+        } = self;
+        n_inputs.encode(enc);
+        n_outputs.encode(enc);
+        connections.encode(enc);
+        nodes.encode(enc)
+    }
+}
+
 pub struct NodeRef<'a> {
     pub inputs: &'a [usize],
     pub outputs: Range<usize>,
@@ -20,6 +36,19 @@ pub struct TopoNode {
     pub n_outputs: usize,
 }
 
+impl Encode for TopoNode {
+    fn encode(&self, enc: &mut Encoder) {
+        let Self {
+            n_local,
+            n_inputs,
+            n_outputs,
+        } = self;
+        n_local.encode(enc);
+        n_inputs.encode(enc);
+        n_outputs.encode(enc)
+    }
+}
+
 impl GraphTopo {
     /// # Safety
     ///
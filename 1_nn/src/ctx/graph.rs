@@ -1,5 +1,7 @@
 ﻿use super::{GraphBuilder, OpLib, Tensor, TensorMeta};
-use crate::{Arg, Dim, Edge, NNError, NNGraph, NuralNetwork, ctx::name::Namespace, op::OpError};
+use crate::{
+    Arg, Dim, Distribution, Edge, NNError, NNGraph, NuralNetwork, ctx::name::Namespace, op::OpError,
+};
 use graph::{GraphTopo, TopoNode};
 use mem::{External, Node, Operator};
 use std::{cell::RefCell, collections::HashMap, fmt::Display, ops::Range, rc::Rc};
@@ -18,6 +20,23 @@ impl GraphBuilder {
         Ok(ctx.into_graph(outputs))
     }
 
+    /// 为张量并行的每个 rank 构造计算图，`dists` 必须按顺序恰好覆盖所有份
+    pub fn build_parallel<T, NN: NuralNetwork<T>>(
+        &self,
+        dists: &[Distribution],
+        mut nn: impl FnMut(Distribution) -> NN,
+        inputs: &[TensorMeta],
+    ) -> Result<Vec<NNGraph<T>>, NNError> {
+        assert!(
+            Distribution::is_partition(dists),
+            "{dists:?} is not a partition"
+        );
+        dists
+            .iter()
+            .map(|&dist| self.build(nn(dist), inputs.iter().cloned()))
+            .collect()
+    }
+
     fn new_context<T>(
         &self,
         global_inputs: impl IntoIterator<Item = TensorMeta>,
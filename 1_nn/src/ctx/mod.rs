@@ -43,3 +43,47 @@ impl OpLib {
         self.0.borrow().get(name).cloned()
     }
 }
+
+/// 注册了所有算子的构图器，供单元测试使用
+#[cfg(test)]
+pub(crate) fn test_builder() -> GraphBuilder {
+    use crate::op::*;
+    let mut builder = GraphBuilder::default();
+    builder
+        .register_op("embedding", embedding::Embedding)
+        .register_op("masked-embedding", embedding::MaskedEmbedding)
+        .register_op("rms-norm", normalization::RmsNorm)
+        .register_op("layer-norm", normalization::LayerNorm)
+        .register_op("attention", attention::Attention)
+        .register_op("mla", mla::Mla)
+        .register_op("rope", rope::Rope)
+        .register_op("mrope", mrope::Mrope)
+        .register_op("linear", linear::Linear)
+        .register_op("quant-linear", linear::QuantLinear)
+        .register_op("matmul", matmul::MatMul)
+        .register_op("conv", conv::Conv)
+        .register_op("swiglu", activation::SwiGLU)
+        .register_op("gelu", activation::GeLU)
+        .register_op("tanh", activation::Tanh)
+        .register_op("add", element_wise::Add)
+        .register_op("mul", element_wise::Mul)
+        .register_op("scale", element_wise::Scale)
+        .register_op("softcap", element_wise::Softcap)
+        .register_op("cast", element_wise::Cast)
+        .register_op("reduce-sum", reduce::ReduceSum)
+        .register_op("index-select", gather::IndexSelect)
+        .register_op("split", split::Split)
+        .register_op("tile", tile::Tile)
+        .register_op("merge", merge::Merge)
+        .register_op("transpose", transpose::Transpose)
+        .register_op("concat", concat::Concat)
+        .register_op("slice", slice::Slice)
+        .register_op("pad", pad::Pad)
+        .register_op("broadcast", broadcast::Broadcast)
+        .register_op("all-reduce", all_reduce::AllReduce)
+        .register_op("all-gather", all_gather::AllGather)
+        .register_op("reduce-scatter", reduce_scatter::ReduceScatter)
+        .register_op("send", p2p::Send)
+        .register_op("recv", p2p::Recv);
+    builder
+}
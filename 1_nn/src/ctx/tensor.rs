@@ -1,6 +1,15 @@
 ﻿use super::Context;
-use crate::{NNError, macros::destruct};
+use crate::{
+    NNError,
+    macros::destruct,
+    op::{
+        OpError,
+        element_wise::{broadcast_shape, dt_name},
+    },
+};
 use arg::{Arg, Dim};
+use graph::{Encode, Encoder};
+use std::fmt;
 use tensor::digit_layout::DigitLayout;
 
 /// 计算图层张量
@@ -106,14 +115,301 @@ impl<T> Tensor<T> {
         );
         Ok(ans)
     }
+
+    pub fn slice(
+        self,
+        name: impl ToString,
+        axis: usize,
+        start: Dim,
+        len: Dim,
+        step: usize,
+    ) -> Result<Tensor<T>, NNError> {
+        destruct!(
+            [ans] = self.ctx.clone().call(
+                name,
+                "slice",
+                Some(Arg::dict([
+                    ("axis".into(), Arg::int(axis)),
+                    ("start".into(), Arg::from(start)),
+                    ("len".into(), Arg::from(len)),
+                    ("step".into(), Arg::int(step)),
+                ])),
+                [self],
+            )?
+        );
+        Ok(ans)
+    }
+
+    pub fn pad(
+        self,
+        name: impl ToString,
+        before: impl IntoIterator<Item = Dim>,
+        after: impl IntoIterator<Item = Dim>,
+        value: f64,
+    ) -> Result<Tensor<T>, NNError> {
+        destruct!(
+            [ans] = self.ctx.clone().call(
+                name,
+                "pad",
+                Some(Arg::dict([
+                    ("before".into(), Arg::arr(before.into_iter().map(Arg::from))),
+                    ("after".into(), Arg::arr(after.into_iter().map(Arg::from))),
+                    ("value".into(), Arg::float(value)),
+                ])),
+                [self],
+            )?
+        );
+        Ok(ans)
+    }
+
+    pub fn broadcast(
+        self,
+        name: impl ToString,
+        shape: impl IntoIterator<Item = Dim>,
+    ) -> Result<Tensor<T>, NNError> {
+        destruct!(
+            [ans] = self.ctx.clone().call(
+                name,
+                "broadcast",
+                Some(Arg::dict([(
+                    "shape".into(),
+                    Arg::arr(shape.into_iter().map(Arg::from))
+                )])),
+                [self],
+            )?
+        );
+        Ok(ans)
+    }
+
+    pub fn add(self, name: impl ToString, rhs: Self) -> Result<Tensor<T>, NNError> {
+        self.binary(name, "add", rhs)
+    }
+
+    pub fn sub(self, name: impl ToString, rhs: Self) -> Result<Tensor<T>, NNError> {
+        self.binary(name, "sub", rhs)
+    }
+
+    pub fn mul(self, name: impl ToString, rhs: Self) -> Result<Tensor<T>, NNError> {
+        self.binary(name, "mul", rhs)
+    }
+
+    pub fn div(self, name: impl ToString, rhs: Self) -> Result<Tensor<T>, NNError> {
+        self.binary(name, "div", rhs)
+    }
+
+    pub fn matmul(self, name: impl ToString, rhs: Self) -> Result<Tensor<T>, NNError> {
+        destruct!([ans] = self.ctx.clone().call(name, "matmul", None, [self, rhs])?);
+        Ok(ans)
+    }
+
+    pub fn scale(self, name: impl ToString, factor: f64) -> Result<Tensor<T>, NNError> {
+        destruct!(
+            [ans] = self
+                .ctx
+                .clone()
+                .call(name, "scale", Some(factor.into()), [self])?
+        );
+        Ok(ans)
+    }
+
+    pub fn softcap(self, name: impl ToString, cap: f64) -> Result<Tensor<T>, NNError> {
+        destruct!(
+            [ans] = self
+                .ctx
+                .clone()
+                .call(name, "softcap", Some(cap.into()), [self])?
+        );
+        Ok(ans)
+    }
+
+    pub fn cast(self, name: impl ToString, dt: DigitLayout) -> Result<Tensor<T>, NNError> {
+        let Some(dt) = dt_name(dt) else {
+            let name = name.to_string();
+            let name = if name.is_empty() { "cast".into() } else { name };
+            return Err(NNError {
+                name: format!("{}:{name}", self.ctx.path()),
+                err: OpError::DataTypeError,
+            });
+        };
+        destruct!(
+            [ans] = self
+                .ctx
+                .clone()
+                .call(name, "cast", Some(dt.into()), [self])?
+        );
+        Ok(ans)
+    }
+
+    pub fn sum(
+        self,
+        name: impl ToString,
+        axes: impl IntoIterator<Item = usize>,
+        keep_dims: bool,
+    ) -> Result<Tensor<T>, NNError> {
+        self.reduce(name, "reduce-sum", axes, keep_dims)
+    }
+
+    pub fn mean(
+        self,
+        name: impl ToString,
+        axes: impl IntoIterator<Item = usize>,
+        keep_dims: bool,
+    ) -> Result<Tensor<T>, NNError> {
+        self.reduce(name, "reduce-mean", axes, keep_dims)
+    }
+
+    pub fn max(
+        self,
+        name: impl ToString,
+        axes: impl IntoIterator<Item = usize>,
+        keep_dims: bool,
+    ) -> Result<Tensor<T>, NNError> {
+        self.reduce(name, "reduce-max", axes, keep_dims)
+    }
+
+    pub fn min(
+        self,
+        name: impl ToString,
+        axes: impl IntoIterator<Item = usize>,
+        keep_dims: bool,
+    ) -> Result<Tensor<T>, NNError> {
+        self.reduce(name, "reduce-min", axes, keep_dims)
+    }
+
+    pub fn argmax(
+        self,
+        name: impl ToString,
+        axis: usize,
+        keep_dims: bool,
+    ) -> Result<Tensor<T>, NNError> {
+        self.reduce(name, "argmax", [axis], keep_dims)
+    }
+
+    pub fn gather(
+        self,
+        name: impl ToString,
+        axis: usize,
+        index: Self,
+    ) -> Result<Tensor<T>, NNError> {
+        self.indexed(name, "gather", axis, [index])
+    }
+
+    pub fn index_select(
+        self,
+        name: impl ToString,
+        axis: usize,
+        index: Self,
+    ) -> Result<Tensor<T>, NNError> {
+        self.indexed(name, "index-select", axis, [index])
+    }
+
+    pub fn scatter(
+        self,
+        name: impl ToString,
+        axis: usize,
+        index: Self,
+        src: Self,
+    ) -> Result<Tensor<T>, NNError> {
+        self.indexed(name, "scatter", axis, [index, src])
+    }
+
+    pub fn index_put(
+        self,
+        name: impl ToString,
+        axis: usize,
+        index: Self,
+        values: Self,
+    ) -> Result<Tensor<T>, NNError> {
+        self.indexed(name, "index-put", axis, [index, values])
+    }
+
+    fn indexed<const N: usize>(
+        self,
+        name: impl ToString,
+        op: &str,
+        axis: usize,
+        others: [Self; N],
+    ) -> Result<Tensor<T>, NNError> {
+        destruct!(
+            [ans] = self.ctx.clone().call(
+                name,
+                op,
+                Some(Arg::dict([("axis".into(), Arg::int(axis))])),
+                std::iter::once(self).chain(others),
+            )?
+        );
+        Ok(ans)
+    }
+
+    fn reduce(
+        self,
+        name: impl ToString,
+        op: &str,
+        axes: impl IntoIterator<Item = usize>,
+        keep_dims: bool,
+    ) -> Result<Tensor<T>, NNError> {
+        destruct!(
+            [ans] = self.ctx.clone().call(
+                name,
+                op,
+                Some(Arg::dict([
+                    ("axes".into(), Arg::arr(axes.into_iter().map(Arg::int))),
+                    ("keep_dims".into(), Arg::bool(keep_dims)),
+                ])),
+                [self],
+            )?
+        );
+        Ok(ans)
+    }
+
+    /// 逐元素二元运算，需要广播的输入先显式广播为输出形状，
+    /// 使存储层可以用步长为 0 的布局实现广播
+    fn binary(self, name: impl ToString, op: &str, rhs: Self) -> Result<Tensor<T>, NNError> {
+        let lhs_shape = self.shape();
+        let rhs_shape = rhs.shape();
+        let (lhs, rhs) = match broadcast_shape(&lhs_shape, &rhs_shape) {
+            Some(shape) => {
+                let one = Dim::from(1);
+                let expand = |s: &[Dim]| {
+                    s.len() != shape.len()
+                        || s.iter()
+                            .zip(&shape)
+                            .any(|(a, b)| a.expr_eq(&one) && !b.expr_eq(&one))
+                };
+                let lhs = if expand(&lhs_shape) {
+                    self.broadcast("", shape.clone())?
+                } else {
+                    self
+                };
+                let rhs = if expand(&rhs_shape) {
+                    rhs.broadcast("", shape)?
+                } else {
+                    rhs
+                };
+                (lhs, rhs)
+            }
+            // 形状不兼容，由算子报告错误
+            None => (self, rhs),
+        };
+        destruct!([ans] = lhs.ctx.clone().call(name, op, None, [lhs, rhs])?);
+        Ok(ans)
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct TensorMeta {
     pub dt: DigitLayout,
     pub shape: Box<[Dim]>,
 }
 
+/// 数据类型按 `DigitLayout` 的编码值编码
+impl Encode for TensorMeta {
+    fn encode(&self, enc: &mut Encoder) {
+        self.dt.to_u64().encode(enc);
+        self.shape.encode(enc)
+    }
+}
+
 impl TensorMeta {
     pub fn new(dt: DigitLayout, shape: impl IntoIterator<Item = Dim>) -> Self {
         let mut shape = shape.into_iter().collect::<Box<_>>();
@@ -136,3 +432,16 @@ impl TensorMeta {
         &self.shape
     }
 }
+
+impl fmt::Display for TensorMeta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[", self.dt)?;
+        for (i, d) in self.shape.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?
+            }
+            write!(f, "{d}")?
+        }
+        write!(f, "]")
+    }
+}
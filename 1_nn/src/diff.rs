@@ -0,0 +1,180 @@
+use crate::{NNGraph, OpInfo, TensorMeta};
+use graph::NodeRef;
+use std::{collections::HashMap, fmt, iter::zip};
+
+/// 两个计算图之间的结构差异，节点按名字（如 `Ω.blk0.attn:attention`）匹配
+#[derive(Clone, Default, Debug)]
+pub struct GraphDiff {
+    /// 只存在于新图中的节点
+    pub added: Vec<String>,
+    /// 只存在于旧图中的节点
+    pub removed: Vec<String>,
+    /// 两图中都存在，但算子、参数或输入输出元信息不同的节点
+    pub changed: Vec<NodeDiff>,
+}
+
+#[derive(Clone, Debug)]
+pub struct NodeDiff {
+    pub name: String,
+    pub old: NodeSummary,
+    pub new: NodeSummary,
+}
+
+/// 节点的结构信息
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NodeSummary {
+    pub op: OpInfo,
+    pub inputs: Box<[TensorMeta]>,
+    pub outputs: Box<[TensorMeta]>,
+}
+
+impl GraphDiff {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl<T> NNGraph<T> {
+    /// 对比 `self`（旧图）和 `new`（新图）的结构差异
+    pub fn diff<U>(&self, new: &NNGraph<U>) -> GraphDiff {
+        let old = self.summaries();
+        let new = new.summaries();
+        let old_map = old.iter().map(|(k, v)| (*k, v)).collect::<HashMap<_, _>>();
+        let new_map = new.iter().map(|(k, v)| (*k, v)).collect::<HashMap<_, _>>();
+
+        let mut ans = GraphDiff::default();
+        for (name, summary) in &old {
+            match new_map.get(name) {
+                Some(&new) if new != summary => ans.changed.push(NodeDiff {
+                    name: name.to_string(),
+                    old: summary.clone(),
+                    new: new.clone(),
+                }),
+                Some(_) => {}
+                None => ans.removed.push(name.to_string()),
+            }
+        }
+        for (name, _) in &new {
+            if !old_map.contains_key(name) {
+                ans.added.push(name.to_string())
+            }
+        }
+        ans
+    }
+
+    fn summaries(&self) -> Vec<(&str, NodeSummary)> {
+        let graph::Graph { topo, nodes, edges } = &self.0;
+        zip(topo.iter(), nodes)
+            .map(|(topo, node)| {
+                let NodeRef { inputs, outputs } = topo;
+                let summary = NodeSummary {
+                    op: node.value.clone(),
+                    inputs: inputs.iter().map(|&i| edges[i].meta.clone()).collect(),
+                    outputs: outputs.map(|i| edges[i].meta.clone()).collect(),
+                };
+                (&*node.name, summary)
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for GraphDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for name in &self.removed {
+            writeln!(f, "- {name}")?
+        }
+        for name in &self.added {
+            writeln!(f, "+ {name}")?
+        }
+        for NodeDiff { name, old, new } in &self.changed {
+            writeln!(f, "~ {name}")?;
+            writeln!(f, "    - {old}")?;
+            writeln!(f, "    + {new}")?
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for NodeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            op,
+            inputs,
+            outputs,
+        } = self;
+        write!(f, "{}", op.name)?;
+        if let Some(arg) = &op.arg {
+            write!(f, "({arg})")?
+        }
+        for (i, meta) in inputs.iter().enumerate() {
+            write!(f, "{}{meta}", if i == 0 { " " } else { ", " })?
+        }
+        write!(f, " ->")?;
+        for (i, meta) in outputs.iter().enumerate() {
+            write!(f, "{}{meta}", if i == 0 { " " } else { ", " })?
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Dim, Linear, NNGraph, NormType, Normalization, OutputHead, TPTensor, ctx::test_builder,
+        digit_layout::types,
+    };
+
+    fn head(softcap: Option<f32>) -> NNGraph<TPTensor<String>> {
+        let head = OutputHead {
+            out_norm: Normalization {
+                d: 32,
+                epsilon: 1e-5,
+                items: NormType::RmsNorm {
+                    dt: types::F32,
+                    scale: "out_norm".to_string(),
+                    offset: 0.,
+                },
+            },
+            lm_head: Linear::new(types::F16, [10, 32], "lm_head".into(), None),
+            softcap,
+            vocab_parallel: None,
+        };
+        let x = crate::TensorMeta::new(types::F16, [Dim::from("n"), 32.into()]);
+        test_builder().build(head.tensor_parallel(), [x]).unwrap()
+    }
+
+    #[test]
+    fn fingerprint() {
+        assert_eq!(head(Some(30.)).fingerprint(), head(Some(30.)).fingerprint());
+        assert_ne!(head(Some(30.)).fingerprint(), head(Some(50.)).fingerprint());
+        assert_ne!(head(None).fingerprint(), head(Some(30.)).fingerprint())
+    }
+
+    #[test]
+    fn diff() {
+        assert!(head(Some(30.)).diff(&head(Some(30.))).is_empty());
+
+        let diff = head(Some(30.)).diff(&head(Some(50.)));
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        let [changed] = &*diff.changed else {
+            panic!("{diff}")
+        };
+        assert!(changed.name.ends_with("logits-softcap"), "{diff}");
+        assert_ne!(changed.old.op.arg, changed.new.op.arg);
+
+        let diff = head(None).diff(&head(Some(30.)));
+        assert!(diff.removed.is_empty() && diff.changed.is_empty());
+        let [added] = &*diff.added else {
+            panic!("{diff}")
+        };
+        assert!(added.ends_with("logits-softcap"), "{diff}");
+
+        let diff = head(Some(30.)).diff(&head(None));
+        assert!(diff.added.is_empty() && diff.changed.is_empty());
+        let [removed] = &*diff.removed else {
+            panic!("{diff}")
+        };
+        assert!(removed.ends_with("logits-softcap"), "{diff}")
+    }
+}
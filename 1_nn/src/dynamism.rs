@@ -0,0 +1,65 @@
+use crate::NNGraph;
+use std::{collections::BTreeSet, ops::Range};
+
+/// 动态性分析得到的一段连续节点，段内每个节点涉及的形状变量相同
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Segment {
+    /// 节点序号范围
+    pub nodes: Range<usize>,
+    /// 段内节点的输入输出形状中出现的变量
+    pub variables: BTreeSet<String>,
+}
+
+impl Segment {
+    /// 段内所有形状都是常量，可以录制为固定的命令序列反复执行
+    #[inline]
+    pub fn is_static(&self) -> bool {
+        self.variables.is_empty()
+    }
+}
+
+impl<T> NNGraph<T> {
+    /// 动态性分析，按节点涉及的形状变量集合将连续的节点分段
+    pub fn dynamism(&self) -> Vec<Segment> {
+        let graph::Graph { topo, edges, .. } = &self.0;
+        let mut ans = Vec::<Segment>::new();
+        for (i, topo) in topo.iter().enumerate() {
+            let mut vars = BTreeSet::new();
+            for j in topo.inputs.iter().cloned().chain(topo.outputs) {
+                for d in edges[j].meta.shape.iter() {
+                    d.append_variables(&mut vars)
+                }
+            }
+            match ans.last_mut() {
+                Some(last)
+                    if last
+                        .variables
+                        .iter()
+                        .map(String::as_str)
+                        .eq(vars.iter().copied()) =>
+                {
+                    last.nodes.end = i + 1
+                }
+                _ => ans.push(Segment {
+                    nodes: i..i + 1,
+                    variables: vars.into_iter().map(String::from).collect(),
+                }),
+            }
+        }
+        ans
+    }
+}
+
+impl<T: Clone> NNGraph<T> {
+    /// 在动态性分段的边界处切分计算图，每段成为一个独立的子图
+    pub fn split_by_dynamism(&self) -> Vec<(Segment, Self)> {
+        self.dynamism()
+            .into_iter()
+            .map(|seg| {
+                // 分段总是非空的
+                let graph = self.subgraph(seg.nodes.clone()).unwrap();
+                (seg, graph)
+            })
+            .collect()
+    }
+}
@@ -1,6 +1,11 @@
 mod ctx;
+mod diff;
+mod dynamism;
 mod nn;
+mod shard;
+mod subgraph;
 
+use graph::Encode;
 use std::collections::HashMap;
 
 pub mod op;
@@ -11,6 +16,8 @@ pub use mem::{BlobLifeTime, Exec, External, Info, Node, Operator as OpInfo};
 pub use tensor::{Tensor, digit_layout, ndarray_layout};
 
 pub use ctx::*;
+pub use diff::{GraphDiff, NodeDiff, NodeSummary};
+pub use dynamism::Segment;
 pub use nn::*;
 
 #[derive(Clone)]
@@ -23,7 +30,42 @@ pub struct Edge<T> {
     pub external: Option<External<T>>,
 }
 
+/// 结构指纹只包含外部张量的名字，不包含其值
+impl<T> Encode for Edge<T> {
+    fn encode(&self, enc: &mut graph::Encoder) {
+        self.meta.encode(enc);
+        self.external.as_ref().map(|e| &e.name).encode(enc)
+    }
+}
+
 impl<T> NNGraph<T> {
+    /// 计算图的结构指纹，包括拓扑、算子、参数、数据类型和形状，不包括外部张量的值
+    pub fn fingerprint(&self) -> u64 {
+        self.0.fingerprint()
+    }
+
+    /// 代入部分形状变量，未绑定的变量保留为符号
+    ///
+    /// 代入的值使某个形状的相等约束不可能成立时返回 `None`。
+    pub fn specialize(self, value: &HashMap<&str, usize>) -> Option<Self> {
+        let Self(graph::Graph {
+            topo,
+            mut nodes,
+            mut edges,
+        }) = self;
+        for node in &mut nodes {
+            if let Some(arg) = &mut node.value.arg {
+                *arg = std::mem::replace(arg, Arg::Bool(false)).partial_substitute(value)?
+            }
+        }
+        for edge in &mut edges {
+            for d in &mut edge.meta.shape {
+                *d = d.partial_substitute(value)?
+            }
+        }
+        Some(Self(graph::Graph { topo, nodes, edges }))
+    }
+
     /// 从逻辑连接图下降到存储管理图
     pub fn lower<U>(
         self,
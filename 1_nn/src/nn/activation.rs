@@ -3,8 +3,38 @@
 #[derive(Clone, Copy)]
 pub enum Activation {
     SwiGLU,
+    /// gelu 门控，Gemma
+    GeGLU,
+    ReGLU,
     SiLU,
     GeLU,
+    GeLUTanh,
+    /// CLIP
+    QuickGeLU,
+    ReLU,
+    SquaredReLU,
+}
+
+impl Activation {
+    /// 门控激活的输入由 gate 和 up 两半拼接而成
+    pub const fn is_gated(&self) -> bool {
+        matches!(self, Self::SwiGLU | Self::GeGLU | Self::ReGLU)
+    }
+
+    /// 对应的算子名字
+    pub const fn op(&self) -> &'static str {
+        match self {
+            Self::SwiGLU => "swiglu",
+            Self::GeGLU => "geglu",
+            Self::ReGLU => "reglu",
+            Self::SiLU => "silu",
+            Self::GeLU => "gelu",
+            Self::GeLUTanh => "gelu-tanh",
+            Self::QuickGeLU => "quick-gelu",
+            Self::ReLU => "relu",
+            Self::SquaredReLU => "squared-relu",
+        }
+    }
 }
 
 impl<T> NuralNetwork<T> for Activation {
@@ -14,23 +44,14 @@ impl<T> NuralNetwork<T> for Activation {
         mut ctx: Context<T>,
     ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
         destruct!([x] = inputs);
-        dims!([_, d] = x);
 
-        let outputs = match self {
-            Self::SwiGLU => {
-                let d = d.clone() / 2;
-                destruct!([gate, up] = x.split("split-gate-up", 1, [d.clone(), d])?);
-                ctx.call("", "swiglu", None, [gate, up])
-            }
-            Self::SiLU => {
-                let d = d.clone() / 2;
-                destruct!([_gate, up] = x.split("split-gate-up", 1, [d.clone(), d])?);
-                ctx.call("", "silu", None, [up])
-            }
-            Self::GeLU => {
-                // format
-                ctx.call("", "gelu", None, [x])
-            }
+        let outputs = if self.is_gated() {
+            dims!([_, d] = x);
+            let d = d.clone() / 2;
+            let parts = x.split("split-gate-up", 1, [d.clone(), d])?;
+            ctx.call("", self.op(), None, parts)
+        } else {
+            ctx.call("", self.op(), None, [x])
         };
 
         Ok((ctx, outputs?))
@@ -4,10 +4,10 @@
 };
 use crate::{
     TPAction,
-    weight_types::{AttnQKV, RowTPWeight},
+    weight_types::{AttnGQA, AttnQKV, ColumnTPWeight, RowTPWeight},
 };
-use arg::Arg;
-use tensor::digit_layout::types;
+use arg::{Arg, Dim};
+use tensor::digit_layout::{DigitLayout, types};
 
 #[derive(Clone)]
 pub struct Attention<T> {
@@ -17,13 +17,28 @@ pub struct Attention<T> {
     pub q_norm: Option<Normalization<T>>,
     pub k_norm: Option<Normalization<T>>,
     pub rope: Option<RoPE<T>>,
+    /// 因果注意力，编码器中为 `false`
+    pub causal: bool,
+    /// 滑动窗口大小
+    pub window: Option<usize>,
+    /// 注意力分数软截断系数
+    pub softcap: Option<f32>,
+    /// ALiBi 斜率 `[nh]`，可以由 [`alibi_slopes`] 生成
+    pub alibi: Option<T>,
+    /// 每个头的注意力汇聚项 `[nh]`
+    pub sinks: Option<(DigitLayout, T)>,
     pub output: Linear<T>,
 }
 
+/// 旋转位置编码，`sin`/`cos` 表可以由 [`RopeTable`](super::RopeTable) 生成。
+///
+/// `dim` 是每个头中参与旋转的维度，小于头维度时只旋转每个头的前 `dim` 维。
+/// 多模态 rope 不支持部分旋转。
 #[derive(Clone)]
 pub struct RoPE<T> {
     pub multimodal: MRoPE,
     pub nctx: usize,
+    pub dim: usize,
     pub sin: T,
     pub cos: T,
 }
@@ -36,6 +51,9 @@ pub enum MRoPE {
 }
 
 impl<T> Attention<T> {
+    /// 按头切分，每个分片取 `dist.range(nh)` 的 q 头，q 头跨越了不完整的 kv 组时 panic。
+    ///
+    /// kv 头不能均分时每个分片只取 q 头所属的 kv 头，kv 头少于分片数时复制，见 [`AttnGQA`]
     pub fn tensor_parallel(self, dist: Distribution) -> Attention<TPTensor<T>> {
         let Self {
             nh,
@@ -44,41 +62,87 @@ impl<T> Attention<T> {
             q_norm,
             k_norm,
             rope,
+            causal,
+            window,
+            softcap,
+            alibi,
+            sinks,
             output,
         } = self;
-        assert_eq!(nh % dist.total, 0);
-        assert_eq!(nkvh % dist.total, 0);
+        let gqa = AttnGQA { nh, nkvh };
+        let Some([q, kv]) = gqa.heads(dist) else {
+            panic!("{dist:?} splits a kv group of {nh} heads with {nkvh} kv heads")
+        };
+        // 以头为单位重新表示切分方式，使分片边界落在头的边界上
+        let q_dist = Distribution::new(q.start, q.len(), nh);
+        let qkv_action = if nkvh.is_multiple_of(dist.total) {
+            TPAction::new(AttnQKV(nh / nkvh), dist)
+        } else {
+            TPAction::new(gqa, dist)
+        };
         Attention {
-            nh: nh / dist.total * dist.len,
-            nkvh: nkvh / dist.total * dist.len,
-            qkv: qkv.parallel(TPAction::new(AttnQKV(nh / nkvh), dist)),
+            nh: q.len(),
+            nkvh: kv.len(),
+            qkv: qkv.parallel(qkv_action),
             q_norm: q_norm.map(|norm| norm.tensor_parallel()),
             k_norm: k_norm.map(|norm| norm.tensor_parallel()),
             rope: rope.map(
                 |RoPE {
                      multimodal,
                      nctx,
+                     dim,
                      sin,
                      cos,
                  }| RoPE {
                     multimodal,
                     nctx,
+                    dim,
                     sin: sin.into(),
                     cos: cos.into(),
                 },
             ),
-            output: output.parallel(TPAction::new(RowTPWeight, dist)),
+            causal,
+            window,
+            softcap,
+            alibi: alibi.map(|val| per_head(val, q_dist)),
+            sinks: sinks.map(|(dt, val)| (dt, per_head(val, q_dist))),
+            output: output.parallel(TPAction::new(RowTPWeight, q_dist)),
         }
     }
 }
 
+/// 按头切分的 `[nh]` 张量
+fn per_head<T>(val: T, dist: Distribution) -> TPTensor<T> {
+    TPTensor {
+        act: (!dist.is_mono()).then(|| TPAction::new(ColumnTPWeight, dist)),
+        val,
+    }
+}
+
+/// BLOOM/MPT 的 ALiBi 斜率，头数不是 2 的幂时按插值方式补充，头数为 0 时返回空表
+pub fn alibi_slopes(nh: usize) -> Box<[f32]> {
+    let Some(log) = nh.checked_ilog2() else {
+        return Box::new([]);
+    };
+    let n = 1 << log;
+    let slopes = |n: usize, step: usize| {
+        let base = 2f32.powf(-8. / n as f32);
+        (0..n).step_by(step).map(move |i| base.powi(i as i32 + 1))
+    };
+    slopes(n, 1).chain(slopes(2 * n, 2).take(nh - n)).collect()
+}
+
 impl<T> NuralNetwork<T> for Attention<T> {
     fn launch(
         self,
         inputs: impl IntoIterator<Item = Tensor<T>>,
         mut ctx: Context<T>,
     ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
-        destruct!([x, pos, residual] = inputs);
+        // 残差是可选的，没有时由外部相加
+        let mut inputs = inputs.into_iter();
+        let x = inputs.next().unwrap();
+        let pos = inputs.next().unwrap();
+        let residual = inputs.next();
 
         let Self {
             nh,
@@ -87,8 +151,15 @@ impl<T> NuralNetwork<T> for Attention<T> {
             q_norm,
             k_norm,
             rope,
+            causal,
+            window,
+            softcap,
+            alibi,
+            sinks,
             output,
         } = self;
+        qkv.check_adapter(&ctx, "attn-qkv")?;
+        output.check_adapter(&ctx, "attn-output")?;
         destruct!([x] = ctx.trap("attn-qkv", qkv, [x])?);
         dims!([_, dqkv] = x);
         let dh = dqkv.clone() / (nh + nkvh + nkvh);
@@ -118,21 +189,39 @@ impl<T> NuralNetwork<T> for Attention<T> {
             Some(RoPE {
                 multimodal,
                 nctx,
+                dim,
                 sin,
                 cos,
             }) => {
                 let (op, shape, arg) = match multimodal {
-                    MRoPE::None => ("rope", [nctx.into(), dh.clone() / 2], None),
-                    MRoPE::MRoPE2D => ("mrope", [nctx.into(), dh.clone() / 4], None),
+                    MRoPE::None => ("rope", [nctx.into(), Dim::from(dim / 2)], None),
+                    MRoPE::MRoPE2D => ("mrope", [nctx.into(), Dim::from(dim / 4)], None),
                     MRoPE::MRoPE3D(section) => (
                         "mrope",
-                        [nctx.into(), dh.clone() / 2],
+                        [nctx.into(), Dim::from(dim / 2)],
                         Some(Arg::arr(section.map(Arg::int))),
                     ),
                 };
                 let sin = ctx.load_external("rope.sin", types::F32, shape.clone(), sin);
                 let cos = ctx.load_external("rope.cos", types::F32, shape, cos);
 
+                // 部分旋转时按头展开，rope 只旋转每个头的前 dim 维
+                let partial = !dh.expr_eq(&Dim::from(dim));
+                assert!(
+                    !partial || matches!(multimodal, MRoPE::None),
+                    "partial rotary is not supported by multimodal rope"
+                );
+                let heads = |x: Tensor<T>, nh: usize| {
+                    if partial {
+                        x.tile("", 1, [nh.into(), dh.clone()])
+                    } else {
+                        Ok(x)
+                    }
+                };
+                let merge = |x: Tensor<T>| if partial { x.merge("", 1, 2) } else { Ok(x) };
+
+                let q = heads(q, nh)?;
+                let k = heads(k, nkvh)?;
                 destruct!(
                     [q_] = ctx.call(
                         "attn-q-rope",
@@ -142,15 +231,91 @@ impl<T> NuralNetwork<T> for Attention<T> {
                     )?
                 );
                 destruct!([k_] = ctx.call("attn-k-rope", op, arg, [k, pos, sin, cos])?);
-                [q_, k_]
+                [merge(q_)?, merge(k_)?]
             }
             None => [q, k],
         };
 
-        destruct!([o] = ctx.call("", "attention", Some(dh.into()), [q, k, v,])?);
+        let mut arg = vec![
+            ("dh".into(), Arg::from(dh)),
+            ("nh".into(), Arg::int(nh)),
+            ("nkvh".into(), Arg::int(nkvh)),
+            ("causal".into(), Arg::bool(causal)),
+            ("alibi".into(), Arg::bool(alibi.is_some())),
+            ("sinks".into(), Arg::bool(sinks.is_some())),
+        ];
+        if let Some(window) = window {
+            arg.push(("window".into(), Arg::int(window)))
+        }
+        if let Some(softcap) = softcap {
+            arg.push(("softcap".into(), Arg::float(softcap as _)))
+        }
+        let mut inputs = vec![q, k, v];
+        if let Some(alibi) = alibi {
+            inputs.push(ctx.load_external("attn-alibi", types::F32, [nh.into()], alibi))
+        }
+        if let Some((dt, sinks)) = sinks {
+            inputs.push(ctx.load_external("attn-sinks", dt, [nh.into()], sinks))
+        }
+        destruct!([o] = ctx.call("", "attention", Some(Arg::dict(arg)), inputs)?);
 
-        let outputs = ctx.trap("attn-output", output, [o, residual]);
+        let outputs = ctx.trap("attn-output", output, std::iter::once(o).chain(residual));
 
         Ok((ctx, outputs?))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Lora,
+        ctx::{TensorMeta, test_builder},
+        op::OpError,
+    };
+
+    /// `d = 32`，4 个 q 头和 2 个 kv 头，qkv 带 `n` 个 LoRA 适配器
+    fn attention(n: usize) -> Attention<String> {
+        let mut qkv = Linear::new(types::F16, [64, 32], "qkv".into(), None);
+        qkv.lora = Some(Lora {
+            dt: types::F16,
+            rank: 4,
+            scale: 1.,
+            adapters: (0..n).map(|i| (format!("a{i}"), format!("b{i}"))).collect(),
+        });
+        Attention {
+            nh: 4,
+            nkvh: 2,
+            qkv,
+            q_norm: None,
+            k_norm: None,
+            rope: None,
+            causal: true,
+            window: None,
+            softcap: None,
+            alibi: None,
+            sinks: None,
+            output: Linear::new(types::F16, [32, 32], "output".into(), None),
+        }
+    }
+
+    #[test]
+    fn multi_adapter() {
+        let inputs = || {
+            [
+                TensorMeta::new(types::F16, [Dim::from("n"), 32.into()]),
+                TensorMeta::new(types::U32, [Dim::from("n")]),
+            ]
+        };
+        let builder = test_builder();
+        assert!(builder.build(attention(1), inputs()).is_ok());
+        // 注意力层中的线性层没有适配器下标输入
+        match builder.build(attention(2), inputs()) {
+            Err(NNError { name, err }) => {
+                assert!(name.ends_with("attn-qkv"), "{name}");
+                assert!(matches!(err, OpError::ArgError))
+            }
+            Ok(_) => panic!("multiple adapters should be rejected"),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use super::{
+    Context, Distribution, Embedding, Linear, NNError, Normalization, NuralNetwork, TPTensor,
+    Table, Tensor, TransformerBlk, llama::launch_blks, macros::destruct,
+};
+
+/// BERT 式编码器，块应该是后归一化的非因果注意力。
+///
+/// 输入依次为 `tokens`、`pos`，有 `token_types` 时还有每个 token 的类型，
+/// 有 `pooler` 时还有每个序列 `[CLS]` 的下标 `cls_idx`。
+/// 输出为每个 token 的隐藏状态，有 `pooler` 时还有池化输出。
+#[derive(Clone)]
+pub struct BertEncoder<T> {
+    /// `wpe` 为位置编码表
+    pub embedding: Embedding<T>,
+    /// 句子类型编码表
+    pub token_types: Option<Table<T>>,
+    pub embd_norm: Normalization<T>,
+    pub blks: Box<[TransformerBlk<T>]>,
+    /// `[CLS]` 的池化投影 `[d, d]`，输出经过 tanh
+    pub pooler: Option<Linear<T>>,
+}
+
+impl<T> BertEncoder<T> {
+    pub fn tensor_parallel(self, dist: Distribution) -> BertEncoder<TPTensor<T>> {
+        let Self {
+            embedding,
+            token_types,
+            embd_norm,
+            blks,
+            pooler,
+        } = self;
+        BertEncoder {
+            embedding: embedding.tensor_parallel(),
+            token_types: token_types.map(|Table { row, weight }| Table {
+                row,
+                weight: weight.into(),
+            }),
+            embd_norm: embd_norm.tensor_parallel(),
+            blks: blks
+                .into_iter()
+                .map(|blk| blk.tensor_parallel(dist))
+                .collect(),
+            pooler: pooler.map(Into::into),
+        }
+    }
+}
+
+impl<T> NuralNetwork<T> for BertEncoder<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            embedding,
+            token_types,
+            embd_norm,
+            blks,
+            pooler,
+        } = self;
+
+        let mut inputs = inputs.into_iter();
+        let tokens = inputs.next().unwrap();
+        let pos = inputs.next().unwrap();
+
+        let (dt, d) = (embedding.dt, embedding.d);
+        destruct!([x] = ctx.trap("embedding", embedding, [tokens, pos.clone()])?);
+        let x = match token_types {
+            Some(Table { row, weight }) => {
+                let types = inputs.next().unwrap();
+                let table = ctx.load_external("token-types", dt, [row.into(), d.into()], weight);
+                destruct!([tt] = ctx.call("token-types", "embedding", None, [table, types])?);
+                x.add("add-token-types", tt)?
+            }
+            None => x,
+        };
+        destruct!([x] = ctx.trap("embd-norm", embd_norm, [x])?);
+
+        let x = launch_blks(&mut ctx, blks, 0, x, pos)?;
+
+        let outputs = match pooler {
+            Some(pooler) => {
+                pooler.check_adapter(&ctx, "pooler")?;
+                let cls_idx = inputs.next().unwrap();
+                let cls = x.clone().index_select("cls-gather", 0, cls_idx)?;
+                destruct!([cls] = ctx.trap("pooler", pooler, [cls])?);
+                destruct!([cls] = ctx.call("pooler-tanh", "tanh", None, [cls])?);
+                vec![x, cls]
+            }
+            None => vec![x],
+        };
+
+        Ok((ctx, outputs))
+    }
+}
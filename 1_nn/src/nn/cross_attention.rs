@@ -0,0 +1,88 @@
+use super::{
+    Context, Distribution, Linear, NNError, NuralNetwork, TPAction, TPTensor, Tensor,
+    macros::*,
+    weight_types::{AttnGQA, ColumnTPWeight, FfnGateUp, RowTPWeight},
+};
+use arg::Arg;
+
+/// 交叉注意力，q 来自解码器，k/v 来自长度不同的编码器输出
+#[derive(Clone)]
+pub struct CrossAttention<T> {
+    pub nh: usize,
+    pub nkvh: usize,
+    /// `[nh * dh, d]`
+    pub q: Linear<T>,
+    /// `[2 * nkvh * dh, d_enc]`，k 和 v 上下拼接
+    pub kv: Linear<T>,
+    pub output: Linear<T>,
+}
+
+impl<T> CrossAttention<T> {
+    /// 按 q 头切分，kv 头按 [`AttnGQA`] 的方式只取 q 头所属的头，kv 头少于分片数时复制
+    pub fn tensor_parallel(self, dist: Distribution) -> CrossAttention<TPTensor<T>> {
+        let Self {
+            nh,
+            nkvh,
+            q,
+            kv,
+            output,
+        } = self;
+        let [q_heads, kv_heads] = AttnGQA { nh, nkvh }.heads(dist).unwrap_or_else(|| {
+            panic!("{dist:?} splits a kv group of {nh} heads with {nkvh} kv heads")
+        });
+        // 以头为单位重新表示切分方式，使分片边界落在头的边界上
+        let q_dist = Distribution::new(q_heads.start, q_heads.len(), nh);
+        let kv_dist = Distribution::new(kv_heads.start, kv_heads.len(), nkvh);
+        CrossAttention {
+            nh: q_heads.len(),
+            nkvh: kv_heads.len(),
+            q: q.parallel(TPAction::new(ColumnTPWeight, q_dist)),
+            // k 和 v 两段分别按头切分，与 gate-up 的切分方式相同
+            kv: kv.parallel(TPAction::new(FfnGateUp, kv_dist)),
+            output: output.parallel(TPAction::new(RowTPWeight, q_dist)),
+        }
+    }
+}
+
+impl<T> NuralNetwork<T> for CrossAttention<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        // 残差是可选的，没有时由外部相加
+        let mut inputs = inputs.into_iter();
+        let x = inputs.next().unwrap();
+        let enc = inputs.next().unwrap();
+        let residual = inputs.next();
+
+        let Self {
+            nh,
+            nkvh,
+            q,
+            kv,
+            output,
+        } = self;
+        q.check_adapter(&ctx, "attn-q")?;
+        kv.check_adapter(&ctx, "attn-kv")?;
+        output.check_adapter(&ctx, "attn-output")?;
+        destruct!([q] = ctx.trap("attn-q", q, [x])?);
+        destruct!([kv] = ctx.trap("attn-kv", kv, [enc])?);
+        dims!([_, dq] = q);
+        let dh = dq.clone() / nh;
+
+        destruct!([k, v] = kv.split("split-kv", 1, [nkvh.into(), nkvh.into()])?);
+
+        let arg = Arg::dict([
+            ("dh".into(), Arg::from(dh)),
+            ("nh".into(), Arg::int(nh)),
+            ("nkvh".into(), Arg::int(nkvh)),
+            ("causal".into(), Arg::bool(false)),
+        ]);
+        destruct!([o] = ctx.call("", "attention", Some(arg), [q, k, v])?);
+
+        let outputs = ctx.trap("attn-output", output, std::iter::once(o).chain(residual));
+
+        Ok((ctx, outputs?))
+    }
+}
@@ -1,7 +1,9 @@
-﻿use std::{any::Any, hash::Hash, rc::Rc};
+﻿use std::{any::Any, hash::Hash, ops::Range, rc::Rc};
 use tensor::Tensor;
 
-/// 分布式切分方式
+/// 分布式切分方式，本分片占 `total` 份中从 `start` 开始的 `len` 份。
+///
+/// 各 rank 的份数可以不同，见 [`Distribution::partition`]。
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Distribution {
     pub start: usize,
@@ -21,6 +23,43 @@ impl Distribution {
         Self { start, len, total }
     }
 
+    /// 按每个 rank 的份数生成各 rank 的切分方式，用于设备数不能均分头数的情况。
+    ///
+    /// 总份数不能整除头数时，按头切分的模块取 `range(nh)` 中的头，
+    /// 再以头为单位重新表示切分方式，使分片边界落在头的边界上
+    pub fn partition(parts: &[usize]) -> Vec<Self> {
+        let total = parts.iter().sum();
+        parts
+            .iter()
+            .scan(0, |start, &len| {
+                let dist = Self::new(*start, len, total);
+                *start += len;
+                Some(dist)
+            })
+            .collect()
+    }
+
+    /// 检查各 rank 的切分方式是否恰好按顺序覆盖所有份
+    pub fn is_partition(dists: &[Self]) -> bool {
+        let Some(total) = dists.first().map(|dist| dist.total) else {
+            return false;
+        };
+        dists
+            .iter()
+            .try_fold(0, |end, dist| {
+                (dist.total == total && dist.start == end).then_some(dist.start + dist.len)
+            })
+            .is_some_and(|end| end == total)
+    }
+
+    /// 长度为 `n` 的维度中属于本分片的范围。
+    ///
+    /// 不能均分时分片边界向下取整，各 rank 的范围仍然恰好拼成完整的维度
+    #[inline]
+    pub const fn range(&self, n: usize) -> Range<usize> {
+        n * self.start / self.total..n * (self.start + self.len) / self.total
+    }
+
     #[inline]
     pub const fn is_mono(&self) -> bool {
         self.len == self.total
@@ -73,6 +112,10 @@ impl Hash for TPAction {
 }
 
 pub trait WeightType: Any {
+    /// 将 `src` 中属于 `dist` 的部分拷贝到 `dst`。
+    ///
+    /// `src` 的形状以数据类型的存储单元为单位，块量化类型的最后一维是块数，
+    /// 因此按行或按列切分时都不会拆开一个量化块。
     fn move_data(&self, dist: Distribution, dst: &mut [u8], src: &Tensor<&[u8], 2>);
     fn split_shape(&self, dist: Distribution, shape: &[usize]) -> Box<[usize]>;
     fn check_eq(&self, other: &dyn WeightType) -> bool;
@@ -85,6 +128,16 @@ pub mod weight_types {
     #[repr(transparent)]
     pub struct AttnQKV(pub usize);
 
+    /// 按 q 头切分的 qkv 权重，每个分片只取 q 头所属的 kv 头。
+    ///
+    /// 分片的 q 头不是完整的若干组时必须落在同一组内，这组的 kv 头在这些分片上复制，
+    /// 因此 kv 头数少于分片数或头数不能均分时也可以切分
+    #[derive(Clone, PartialEq, Eq)]
+    pub struct AttnGQA {
+        pub nh: usize,
+        pub nkvh: usize,
+    }
+
     #[derive(Clone, PartialEq, Eq)]
     #[repr(transparent)]
     pub struct FfnGateUp;
@@ -138,27 +191,88 @@ pub mod weight_types {
         }
     }
 
+    impl AttnGQA {
+        /// 本分片的 q 头和 kv 头范围，q 头跨越了不完整的组时返回 `None`
+        pub fn heads(&self, dist: Distribution) -> Option<[Range<usize>; 2]> {
+            let &Self { nh, nkvh } = self;
+            assert_eq!(nh % nkvh, 0);
+            let group = nh / nkvh;
+            let q = dist.range(nh);
+            let kv = if q.is_empty() {
+                return None;
+            } else if q.start.is_multiple_of(group) && q.end.is_multiple_of(group) {
+                q.start / group..q.end / group
+            } else if q.start / group == (q.end - 1) / group {
+                let kv = q.start / group;
+                kv..kv + 1
+            } else {
+                return None;
+            };
+            Some([q, kv])
+        }
+
+        fn split(&self, dist: Distribution) -> [Range<usize>; 2] {
+            self.heads(dist).unwrap_or_else(|| {
+                let Self { nh, nkvh } = self;
+                panic!("{dist:?} splits a kv group of {nh} heads with {nkvh} kv heads")
+            })
+        }
+    }
+
+    impl WeightType for AttnGQA {
+        impl_wt_eq!();
+        fn move_data(&self, dist: Distribution, dst: &mut [u8], src: &Tensor<&[u8], 2>) {
+            assert!(src.is_contiguous());
+            let &Self { nh, nkvh } = self;
+            let [q, kv] = self.split(dist);
+
+            assert_eq!(src.shape()[0] % (nh + 2 * nkvh), 0);
+
+            let src = *src.get();
+            let head = src.len() / (nh + 2 * nkvh);
+            let mut dst = dst.chunks_exact_mut(head);
+            let k = kv.start + nh..kv.end + nh;
+            let v = k.start + nkvh..k.end + nkvh;
+            for i in q.chain(k).chain(v) {
+                dst.next()
+                    .unwrap()
+                    .copy_from_slice(&src[i * head..][..head])
+            }
+            assert!(dst.next().is_none())
+        }
+
+        fn split_shape(&self, dist: Distribution, shape: &[usize]) -> Box<[usize]> {
+            let &Self { nh, nkvh } = self;
+            let [q, kv] = self.split(dist);
+            let rows = |r: usize| r / (nh + 2 * nkvh) * (q.len() + 2 * kv.len());
+            match *shape {
+                [r] => [rows(r)].into(),
+                [r, c] => [rows(r), c].into(),
+                [..] => unreachable!(),
+            }
+        }
+    }
+
     impl WeightType for FfnGateUp {
         impl_wt_eq!();
         fn move_data(&self, dist: Distribution, dst: &mut [u8], src: &Tensor<&[u8], 2>) {
             assert!(src.is_contiguous());
-            let Distribution { start, len, total } = dist;
 
             assert_eq!(src.shape()[0] % 2, 0);
-            assert_eq!(src.shape()[0] / 2 % total, 0);
 
+            let rows = src.shape()[0] / 2;
             let src = *src.get();
-            let shard = src.len() / 2;
-            let piece = shard / total;
-            dst[..len * piece].copy_from_slice(&src[start * piece..][..len * piece]);
-            dst[len * piece..].copy_from_slice(&src[shard..][start * piece..][..len * piece]);
+            let line = src.len() / 2 / rows;
+            let range = dist.range(rows);
+            let piece = range.len() * line;
+            dst[..piece].copy_from_slice(&src[range.start * line..][..piece]);
+            dst[piece..].copy_from_slice(&src[(rows + range.start) * line..][..piece]);
         }
 
         fn split_shape(&self, dist: Distribution, shape: &[usize]) -> Box<[usize]> {
-            let Distribution { len, total, .. } = dist;
             match *shape {
-                [r] => [r / total * len].into(),
-                [r, c] => [r / total * len, c].into(),
+                [r] => [dist.range(r / 2).len() * 2].into(),
+                [r, c] => [dist.range(r / 2).len() * 2, c].into(),
                 [..] => unreachable!(),
             }
         }
@@ -168,20 +282,18 @@ pub mod weight_types {
         impl_wt_eq!();
         fn move_data(&self, dist: Distribution, dst: &mut [u8], src: &Tensor<&[u8], 2>) {
             assert!(src.is_contiguous());
-            let Distribution { start, len, total } = dist;
-
-            assert_eq!(src.shape()[0] % total, 0);
 
+            let rows = src.shape()[0];
             let src = *src.get();
-            let piece = src.len() / total;
-            dst.copy_from_slice(&src[start * piece..][..len * piece]);
+            let line = src.len() / rows;
+            let range = dist.range(rows);
+            dst.copy_from_slice(&src[range.start * line..range.end * line]);
         }
 
         fn split_shape(&self, dist: Distribution, shape: &[usize]) -> Box<[usize]> {
-            let Distribution { len, total, .. } = dist;
             match *shape {
-                [r] => [r / total * len].into(),
-                [r, c] => [r / total * len, c].into(),
+                [r] => [dist.range(r).len()].into(),
+                [r, c] => [dist.range(r).len(), c].into(),
                 [..] => unreachable!(),
             }
         }
@@ -191,18 +303,16 @@ pub mod weight_types {
         impl_wt_eq!();
         fn move_data(&self, dist: Distribution, dst: &mut [u8], src: &Tensor<&[u8], 2>) {
             assert!(src.is_contiguous());
-            let Distribution { start, len, total } = dist;
-
             match src.layout().ndim() {
                 1 => dst.copy_from_slice(src.get()),
                 2 => {
                     use mem_rearrange::Rearranging;
 
-                    assert_eq!(src.shape()[1] % total, 0);
-                    let piece = src.shape()[1] / total;
+                    // 块量化类型按块切分，形状以块为单位，不会拆开一个块
+                    let cols = dist.range(src.shape()[1]);
                     let src = src
                         .as_deref()
-                        .transform(|layout| layout.slice(1, start * piece, 1, len * piece))
+                        .transform(|layout| layout.slice(1, cols.start, 1, cols.len()))
                         .map(|slice| slice.as_ptr());
                     let mut dst = src.use_info().map(|len| {
                         assert_eq!(size_of_val(dst), len);
@@ -218,10 +328,9 @@ pub mod weight_types {
         }
 
         fn split_shape(&self, dist: Distribution, shape: &[usize]) -> Box<[usize]> {
-            let Distribution { len, total, .. } = dist;
             match *shape {
                 [r] => [r].into(),
-                [r, c] => [r, c / total * len].into(),
+                [r, c] => [r, dist.range(c).len()].into(),
                 [..] => unreachable!(),
             }
         }
@@ -1,4 +1,7 @@
-﻿use super::{Context, NNError, NuralNetwork, TPTensor, Tensor};
+﻿use super::{
+    Context, Distribution, NNError, NuralNetwork, TPAction, TPTensor, Tensor, macros::destruct,
+    weight_types::ColumnTPWeight,
+};
 use arg::Arg;
 use tensor::digit_layout::DigitLayout;
 
@@ -9,6 +12,10 @@ pub struct Embedding<T> {
     pub wte: Table<T>,
     pub wpe: Option<Table<T>>,
     pub img_info: Option<[usize; 3]>,
+    /// 输出的缩放系数，Gemma 为 `sqrt(d)`
+    pub scale: Option<f32>,
+    /// 词表并行的切分方式，每个 rank 只保存 `wte` 的一部分行，查表的结果 all-reduce
+    pub vocab_parallel: Option<Distribution>,
 }
 
 #[derive(Clone)]
@@ -25,6 +32,8 @@ impl<T> Embedding<T> {
             wte,
             wpe,
             img_info,
+            scale,
+            ..
         } = self;
         Embedding {
             dt,
@@ -38,6 +47,42 @@ impl<T> Embedding<T> {
                 weight: weight.into(),
             }),
             img_info,
+            scale,
+            vocab_parallel: None,
+        }
+    }
+
+    /// 按行切分 `wte`，词表不能均分时各分片的行数不同，有图像输入时保留完整的词表
+    pub fn vocab_parallel(self, dist: Distribution) -> Embedding<TPTensor<T>> {
+        if dist.is_mono() || self.img_info.is_some() {
+            return self.tensor_parallel();
+        }
+        let Self {
+            dt,
+            d,
+            wte,
+            wpe,
+            img_info,
+            scale,
+            ..
+        } = self;
+        Embedding {
+            dt,
+            d,
+            wte: Table {
+                row: wte.row,
+                weight: TPTensor {
+                    act: Some(TPAction::new(ColumnTPWeight, dist)),
+                    val: wte.weight,
+                },
+            },
+            wpe: wpe.map(|Table { row, weight }| Table {
+                row,
+                weight: weight.into(),
+            }),
+            img_info,
+            scale,
+            vocab_parallel: Some(dist),
         }
     }
 }
@@ -54,30 +99,59 @@ impl<T> NuralNetwork<T> for Embedding<T> {
             wte,
             wpe,
             img_info,
+            scale,
+            vocab_parallel,
         } = self;
         let mut inputs = inputs.into_iter();
 
         let Table { row, weight } = wte;
-        let wte = ctx.load_external("wte", dt, [row.into(), d.into()], weight);
         let tokens = inputs.next().unwrap();
 
-        let arg = img_info
-            .as_ref()
-            .map(|x| Arg::arr(x.iter().map(|&val| Arg::int(val))));
-
-        let outputs = match wpe {
-            Some(wpe) => {
-                let Table { row, weight } = wpe;
-                let wpe = ctx.load_external("wpe", dt, [row.into(), d.into()], weight);
-                let pos = inputs.next().unwrap();
-                ctx.call("", "embedding", arg, [wte, tokens, wpe, pos])
+        let x = match vocab_parallel {
+            Some(dist) => {
+                // 各分片查表的结果相加，位置编码在规约之后加
+                let rows = dist.range(row);
+                let wte = ctx.load_external("wte", dt, [rows.len().into(), d.into()], weight);
+                let arg = Some(Arg::int(rows.start));
+                destruct!([x] = ctx.call("", "masked-embedding", arg, [wte, tokens])?);
+                destruct!([x] = ctx.call("", "all-reduce", Some("sum".into()), [x])?);
+                match wpe {
+                    Some(Table { row, weight }) => {
+                        let wpe = ctx.load_external("wpe", dt, [row.into(), d.into()], weight);
+                        let pos = inputs.next().unwrap();
+                        destruct!([p] = ctx.call("wpe", "embedding", None, [wpe, pos])?);
+                        x.add("wpe-add", p)?
+                    }
+                    None => x,
+                }
             }
             None => {
-                // format
-                ctx.call("", "embedding", arg, [wte, tokens])
+                let wte = ctx.load_external("wte", dt, [row.into(), d.into()], weight);
+                let arg = img_info
+                    .as_ref()
+                    .map(|x| Arg::arr(x.iter().map(|&val| Arg::int(val))));
+                let outputs = match wpe {
+                    Some(wpe) => {
+                        let Table { row, weight } = wpe;
+                        let wpe = ctx.load_external("wpe", dt, [row.into(), d.into()], weight);
+                        let pos = inputs.next().unwrap();
+                        ctx.call("", "embedding", arg, [wte, tokens, wpe, pos])
+                    }
+                    None => {
+                        // format
+                        ctx.call("", "embedding", arg, [wte, tokens])
+                    }
+                };
+                destruct!([x] = outputs?);
+                x
             }
         };
 
-        Ok((ctx, outputs?))
+        let x = match scale {
+            Some(scale) => x.scale("scale", scale as _)?,
+            None => x,
+        };
+
+        Ok((ctx, vec![x]))
     }
 }
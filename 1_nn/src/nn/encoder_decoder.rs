@@ -0,0 +1,240 @@
+use super::{
+    Collective, Context, CrossAttention, Distribution, Embedding, Mlp, NNError, NormPlacement,
+    Normalization, NuralNetwork, OutputHead, ResidualScale, SelfAttn, TPTensor, Tensor,
+    TransformerBlk, macros::destruct, transformer_blk::Blk,
+};
+
+/// 编码器，块中的自注意力应该是非因果的
+#[derive(Clone)]
+pub struct Encoder<T> {
+    pub embedding: Embedding<T>,
+    pub blks: Box<[TransformerBlk<T>]>,
+    pub out_norm: Option<Normalization<T>>,
+}
+
+/// 解码器块，在自注意力和前馈之间关注编码器输出。
+///
+/// 归一化的位置和残差缩放与 [`TransformerBlk`] 相同，三明治归一化的输出归一化只作用于自注意力和前馈
+#[derive(Clone)]
+pub struct DecoderBlk<T> {
+    pub attn_norm: Normalization<T>,
+    pub attn: SelfAttn<T>,
+    pub cross_norm: Normalization<T>,
+    pub cross: CrossAttention<T>,
+    pub ffn_norm: Normalization<T>,
+    pub ffn: Mlp<T>,
+    pub norm: NormPlacement<T>,
+    pub residual: ResidualScale,
+    pub collective: Collective,
+}
+
+/// T5/Whisper/BART 式的编码器-解码器。
+///
+/// 输入依次为编码器的 `tokens`、`pos`，解码器的 `tokens`、`pos`，以及选取输出行的 `out_idx`，
+/// 输出为编码器输出和解码器的 logits。
+/// `encoder` 为 `None` 时只构造解码器，编码器的两个输入替换为缓存的编码器输出，只输出 logits。
+#[derive(Clone)]
+pub struct EncoderDecoder<T> {
+    pub encoder: Option<Encoder<T>>,
+    pub embedding: Embedding<T>,
+    pub blks: Box<[DecoderBlk<T>]>,
+    pub output_head: OutputHead<T>,
+}
+
+impl<T> Encoder<T> {
+    pub fn tensor_parallel(self, dist: Distribution) -> Encoder<TPTensor<T>> {
+        let Self {
+            embedding,
+            blks,
+            out_norm,
+        } = self;
+        Encoder {
+            embedding: embedding.tensor_parallel(),
+            blks: blks
+                .into_iter()
+                .map(|blk| blk.tensor_parallel(dist))
+                .collect(),
+            out_norm: out_norm.map(Normalization::tensor_parallel),
+        }
+    }
+}
+
+impl<T> DecoderBlk<T> {
+    #[inline]
+    pub fn new(
+        attn_norm: Normalization<T>,
+        attn: impl Into<SelfAttn<T>>,
+        cross_norm: Normalization<T>,
+        cross: CrossAttention<T>,
+        ffn_norm: Normalization<T>,
+        ffn: Mlp<T>,
+    ) -> Self {
+        Self {
+            attn_norm,
+            attn: attn.into(),
+            cross_norm,
+            cross,
+            ffn_norm,
+            ffn,
+            norm: NormPlacement::Pre,
+            residual: ResidualScale::default(),
+            collective: Collective::None,
+        }
+    }
+
+    pub fn tensor_parallel(self, dist: Distribution) -> DecoderBlk<TPTensor<T>> {
+        let Self {
+            attn_norm,
+            attn,
+            cross_norm,
+            cross,
+            ffn_norm,
+            ffn,
+            norm,
+            residual,
+            ..
+        } = self;
+        DecoderBlk {
+            attn_norm: attn_norm.tensor_parallel(),
+            attn: attn.tensor_parallel(dist),
+            cross_norm: cross_norm.tensor_parallel(),
+            cross: cross.tensor_parallel(dist),
+            ffn_norm: ffn_norm.tensor_parallel(),
+            ffn: ffn.tensor_parallel(dist),
+            norm: norm.tensor_parallel(),
+            residual,
+            collective: if dist.is_mono() {
+                Collective::None
+            } else {
+                Collective::AllReduce
+            },
+        }
+    }
+}
+
+impl<T> EncoderDecoder<T> {
+    pub fn tensor_parallel(self, dist: Distribution) -> EncoderDecoder<TPTensor<T>> {
+        let Self {
+            encoder,
+            embedding,
+            blks,
+            output_head,
+        } = self;
+        EncoderDecoder {
+            encoder: encoder.map(|encoder| encoder.tensor_parallel(dist)),
+            embedding: embedding.tensor_parallel(),
+            blks: blks
+                .into_iter()
+                .map(|blk| blk.tensor_parallel(dist))
+                .collect(),
+            output_head: output_head.tensor_parallel(),
+        }
+    }
+}
+
+impl<T> NuralNetwork<T> for Encoder<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            embedding,
+            blks,
+            out_norm,
+        } = self;
+
+        destruct!([tokens, pos] = inputs);
+        destruct!([x] = ctx.trap("embedding", embedding, [tokens, pos.clone()])?);
+
+        let x = blks.into_iter().enumerate().try_fold(x, |x, (i, blk)| {
+            destruct!([x] = ctx.trap(format!("blk{i}"), blk, [x, pos.clone()])?);
+            Ok(x)
+        })?;
+
+        let x = match out_norm {
+            Some(norm) => {
+                destruct!([x] = ctx.trap("out-norm", norm, [x])?);
+                x
+            }
+            None => x,
+        };
+
+        Ok((ctx, vec![x]))
+    }
+}
+
+impl<T> NuralNetwork<T> for DecoderBlk<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            attn_norm,
+            attn,
+            cross_norm,
+            cross,
+            ffn_norm,
+            ffn,
+            norm,
+            residual,
+            collective,
+        } = self;
+        let cross_norms = norm.sub(cross_norm);
+        let [attn_norms, ffn_norms] = norm.place(attn_norm, ffn_norm);
+
+        destruct!([x, pos, enc] = inputs);
+        let blk = Blk {
+            collective,
+            residual,
+        };
+        let x = blk.sublayer(&mut ctx, "attn", attn, attn_norms, x, Some(pos))?;
+        let x = blk.sublayer(&mut ctx, "cross", cross, cross_norms, x, Some(enc))?;
+        let x = blk.sublayer(&mut ctx, "ffn", ffn, ffn_norms, x, None)?;
+
+        Ok((ctx, vec![x]))
+    }
+}
+
+impl<T> NuralNetwork<T> for EncoderDecoder<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            encoder,
+            embedding,
+            blks,
+            output_head,
+        } = self;
+
+        let mut inputs = inputs.into_iter();
+        let (enc, cached) = match encoder {
+            Some(encoder) => {
+                let tokens = inputs.next().unwrap();
+                let pos = inputs.next().unwrap();
+                destruct!([enc] = ctx.trap("encoder", encoder, [tokens, pos])?);
+                (enc, false)
+            }
+            None => (inputs.next().unwrap(), true),
+        };
+
+        let tokens = inputs.next().unwrap();
+        let pos = inputs.next().unwrap();
+        let out_idx = inputs.next().unwrap();
+
+        destruct!([x] = ctx.trap("embedding", embedding, [tokens, pos.clone()])?);
+        let x = blks.into_iter().enumerate().try_fold(x, |x, (i, blk)| {
+            destruct!([x] = ctx.trap(format!("blk{i}"), blk, [x, pos.clone(), enc.clone()])?);
+            Ok(x)
+        })?;
+
+        let x = x.index_select("out-gather", 0, out_idx)?;
+        destruct!([x] = ctx.trap("output", output_head, [x])?);
+
+        let outputs = if cached { vec![x] } else { vec![enc, x] };
+        Ok((ctx, outputs))
+    }
+}
@@ -0,0 +1,57 @@
+use super::{
+    Context, Distribution, Embedding, NNError, NuralNetwork, OutputHead, TPTensor, Tensor,
+    TransformerBlk,
+    llama::{launch_blks, launch_head},
+    macros::destruct,
+};
+
+/// GPT-2 式解码器，使用学习的位置编码表，块为前归一化的层归一化和带偏置的 GeLU 前馈。
+///
+/// 输入依次为 `tokens`、`pos`，有输出头时还有选取输出行的 `out_idx`。
+#[derive(Clone)]
+pub struct GPT2<T> {
+    /// `wpe` 为位置编码表
+    pub embedding: Embedding<T>,
+    pub blks: Box<[TransformerBlk<T>]>,
+    pub output_head: Option<OutputHead<T>>,
+}
+
+impl<T> GPT2<T> {
+    pub fn tensor_parallel(self, dist: Distribution) -> GPT2<TPTensor<T>> {
+        let Self {
+            embedding,
+            blks,
+            output_head,
+        } = self;
+        GPT2 {
+            embedding: embedding.tensor_parallel(),
+            blks: blks
+                .into_iter()
+                .map(|blk| blk.tensor_parallel(dist))
+                .collect(),
+            output_head: output_head.map(OutputHead::tensor_parallel),
+        }
+    }
+}
+
+impl<T> NuralNetwork<T> for GPT2<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            embedding,
+            blks,
+            output_head,
+        } = self;
+
+        let mut inputs = inputs.into_iter();
+        let tokens = inputs.next().unwrap();
+        let pos = inputs.next().unwrap();
+
+        destruct!([x] = ctx.trap("embedding", embedding, [tokens, pos.clone()])?);
+        let x = launch_blks(&mut ctx, blks, 0, x, pos)?;
+        launch_head(ctx, output_head, x, inputs)
+    }
+}
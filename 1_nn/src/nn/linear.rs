@@ -1,5 +1,5 @@
 ﻿use super::{
-    Context, Distribution, NNError, NuralNetwork, TPAction, TPTensor, Tensor,
+    Context, Lora, NNError, NuralNetwork, OpError, TPAction, TPTensor, Tensor, macros::destruct,
     weight_types::RowTPWeight,
 };
 use std::any::Any;
@@ -12,6 +12,7 @@ pub struct Linear<T> {
     pub weight: T,
     pub bias: Option<(DigitLayout, T)>,
     pub allow_residual: bool,
+    pub lora: Option<Lora<T>>,
 }
 
 impl<T> Linear<T> {
@@ -28,6 +29,38 @@ impl<T> Linear<T> {
             weight,
             bias,
             allow_residual: true,
+            lora: None,
+        }
+    }
+
+    /// 把第 `i` 个适配器合并到权重中并移除所有适配器，
+    /// `merge(weight, a, b, scale)` 返回合并后的权重，见 [`merge_lora`](super::merge_lora)
+    pub fn merge_adapter(mut self, i: usize, merge: impl FnOnce(T, T, T, f32) -> T) -> Self {
+        let Lora {
+            scale, adapters, ..
+        } = self.lora.take().expect("no LoRA adapter to merge");
+        assert_eq!(
+            self.dt.group_size(),
+            1,
+            "cannot merge LoRA into quantized weight"
+        );
+        let (a, b) = adapters
+            .into_iter()
+            .nth(i)
+            .expect("adapter index out of range");
+        self.weight = merge(self.weight, a, b, scale);
+        self
+    }
+
+    /// 多个适配器需要适配器下标作为额外的输入，其他模块中的线性层没有这个输入，构图时报错
+    pub(super) fn check_adapter(&self, ctx: &Context<T>, name: &str) -> Result<(), NNError> {
+        if self.lora.as_ref().is_some_and(Lora::is_multi) {
+            Err(NNError {
+                name: format!("{}:{name}", ctx.path()),
+                err: OpError::ArgError,
+            })
+        } else {
+            Ok(())
         }
     }
 
@@ -38,19 +71,18 @@ impl<T> Linear<T> {
             weight,
             bias,
             allow_residual,
+            lora,
         } = self;
-        let (act, allow_residual) = if !tp_action.dist.is_mono() {
-            let [r, c] = &mut shape;
-            let Distribution { start, len, total } = tp_action.dist;
-            if (*tp_action.wt).type_id() == RowTPWeight.type_id() {
-                *c = *c / total * len
-            } else {
-                *r = *r / total * len
-            }
-            (Some(tp_action), allow_residual && start == 0)
+        let (act, allow_residual, bias, is_row) = if !tp_action.dist.is_mono() {
+            let first = tp_action.dist.start == 0;
+            // 行切分的结果需要规约，偏置和残差一样只在第一个分片上相加
+            let is_row = split_shape(&tp_action, &mut shape, dt.group_size());
+            let bias = if is_row { bias.filter(|_| first) } else { bias };
+            (Some(tp_action), allow_residual && first, bias, is_row)
         } else {
-            (None, allow_residual)
+            (None, allow_residual, bias, false)
         };
+        let lora = lora.map(|lora| lora.parallel(act.clone(), is_row));
         Linear {
             dt,
             shape,
@@ -60,10 +92,53 @@ impl<T> Linear<T> {
             },
             bias: bias.map(|(dt, val)| (dt, TPTensor { act, val })),
             allow_residual,
+            lora,
         }
     }
 }
 
+/// 不切分，每个分片持有完整的权重
+impl<T> From<Linear<T>> for Linear<TPTensor<T>> {
+    fn from(value: Linear<T>) -> Self {
+        let Linear {
+            dt,
+            shape,
+            weight,
+            bias,
+            allow_residual,
+            lora,
+        } = value;
+        Self {
+            dt,
+            shape,
+            weight: weight.into(),
+            bias: bias.map(|(dt, bias)| (dt, bias.into())),
+            allow_residual,
+            lora: lora.map(|lora| lora.parallel(None, false)),
+        }
+    }
+}
+
+/// 按张量并行的切分方式修改权重形状 `[r, c]`，返回是否为行切分。
+///
+/// 行切分沿 `c` 切分，分片的边界必须是 `unit` 的倍数，以免拆开量化块
+pub(super) fn split_shape(tp_action: &TPAction, shape: &mut [usize; 2], unit: usize) -> bool {
+    let TPAction { wt, dist } = tp_action;
+    let is_row = (**wt).type_id() == RowTPWeight.type_id();
+    if is_row {
+        let cols = dist.range(shape[1]);
+        assert!(
+            cols.start % unit == 0 && cols.end % unit == 0,
+            "row tensor parallel must not split a quantization block"
+        );
+    }
+    let [r, c] = *wt.split_shape(*dist, &*shape) else {
+        unreachable!()
+    };
+    *shape = [r, c];
+    is_row
+}
+
 impl<T> NuralNetwork<T> for Linear<T> {
     fn launch(
         self,
@@ -76,12 +151,20 @@ impl<T> NuralNetwork<T> for Linear<T> {
             weight,
             bias,
             allow_residual,
+            lora,
         } = self;
         let [r, c] = shape;
         let w = ctx.load_external("weight", dt, [r.into(), c.into()], weight);
 
+        let mut inputs = inputs.into_iter().collect::<Vec<_>>();
+        // 多个适配器时最后一个输入是适配器下标
+        let index = lora
+            .as_ref()
+            .filter(|lora| lora.is_multi())
+            .map(|_| inputs.pop().unwrap());
         let mut inputs = inputs.into_iter();
         let x = inputs.next().unwrap();
+        let lora = lora.map(|lora| (lora, x.clone()));
         let outputs = match inputs.next() {
             Some(residual) if allow_residual => match bias {
                 Some((dt, bias)) => {
@@ -104,6 +187,13 @@ impl<T> NuralNetwork<T> for Linear<T> {
                 }
             },
         };
+        let outputs = match lora {
+            Some((lora, x)) => {
+                destruct!([y] = outputs?);
+                Ok(vec![lora.launch(&mut ctx, shape, x, y, index)?])
+            }
+            None => outputs,
+        };
 
         Ok((ctx, outputs?))
     }
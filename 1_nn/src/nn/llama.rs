@@ -1,7 +1,8 @@
 ﻿use super::{
-    Context, Distribution, Embedding, NNError, NuralNetwork, TPTensor, Tensor, TransformerBlk,
-    macros::destruct, output_head::OutputHead,
+    Collective, Context, Distribution, Embedding, NNError, NuralNetwork, TPTensor, Tensor,
+    TransformerBlk, macros::destruct, output_head::OutputHead,
 };
+use crate::Arg;
 
 #[derive(Clone)]
 pub struct LLaMA<T> {
@@ -10,22 +11,170 @@ pub struct LLaMA<T> {
     pub output_head: Option<OutputHead<T>>,
 }
 
+/// LLaMA 的一个流水线阶段，包含连续的若干块，第一个阶段包含嵌入，最后一个阶段包含输出头。
+///
+/// 输入为 `[x, pos, out_idx]`，第一个阶段的 `x` 是词表下标，其他阶段的 `x` 是隐状态的占位，
+/// 由 `recv` 从上一个阶段接收；`out_idx` 只有最后一个阶段有输出头时存在。
+/// 不是最后一个阶段时，把隐状态 `send` 给下一个阶段，没有输出。
+/// 序列并行时阶段之间传递的隐状态只包含本 rank 的 token。
+/// `send`/`recv` 的参数是对方的阶段序号，张量并行时与对方阶段中相同 rank 的分片通信，
+/// 全局 rank 的映射见 `exec::RankLayout`。
+#[derive(Clone)]
+pub struct LLaMAStage<T> {
+    pub stage: usize,
+    pub n_stages: usize,
+    /// 第一个块在完整模型中的序号，使节点名与完整模型一致
+    pub offset: usize,
+    pub embedding: Option<Embedding<T>>,
+    pub blks: Box<[TransformerBlk<T>]>,
+    pub output_head: Option<OutputHead<T>>,
+}
+
 impl<T> LLaMA<T> {
+    /// 张量并行，嵌入和输出头在每个 rank 上保留完整的词表
     pub fn tensor_parallel(self, dist: Distribution) -> LLaMA<TPTensor<T>> {
+        self.parallel(dist, false, false)
+    }
+
+    /// 张量并行并且块之间的激活按 token 切分，见 [`TransformerBlk::sequence_parallel`]
+    pub fn sequence_parallel(self, dist: Distribution) -> LLaMA<TPTensor<T>> {
+        self.parallel(dist, true, false)
+    }
+
+    /// 张量并行并且嵌入和输出头按词表切分，见 [`Embedding::vocab_parallel`] 和
+    /// [`OutputHead::vocab_parallel`]，`sequence` 为 `true` 时块同 [`Self::sequence_parallel`]
+    pub fn vocab_parallel(self, dist: Distribution, sequence: bool) -> LLaMA<TPTensor<T>> {
+        self.parallel(dist, sequence, true)
+    }
+
+    fn parallel(self, dist: Distribution, sequence: bool, vocab: bool) -> LLaMA<TPTensor<T>> {
         let Self {
             embedding,
             blks,
             output_head,
         } = self;
         LLaMA {
-            embedding: embedding.tensor_parallel(),
-            blks: blks
-                .into_iter()
-                .map(|blk| blk.tensor_parallel(dist))
-                .collect(),
-            output_head: output_head.map(OutputHead::tensor_parallel),
+            embedding: parallel_embedding(embedding, dist, vocab),
+            blks: parallel_blks(blks, dist, sequence),
+            output_head: output_head.map(|head| parallel_head(head, dist, vocab)),
         }
     }
+
+    /// 在 `bounds` 指定的块序号处切分为流水线阶段，`bounds` 严格递增且不包括 0 和块数。
+    ///
+    /// 每个阶段的张量并行分片用 [`GraphBuilder::build_parallel`](crate::GraphBuilder::build_parallel) 构造
+    pub fn pipeline(self, bounds: &[usize]) -> Vec<LLaMAStage<T>> {
+        let Self {
+            embedding,
+            blks,
+            output_head,
+        } = self;
+        let nblk = blks.len();
+        assert!(
+            bounds
+                .iter()
+                .try_fold(0, |last, &b| (last < b && b < nblk).then_some(b))
+                .is_some(),
+            "invalid pipeline bounds {bounds:?} for {nblk} blocks"
+        );
+
+        let n_stages = bounds.len() + 1;
+        let mut embedding = Some(embedding);
+        let mut output_head = output_head;
+        let mut blks = blks.into_vec().into_iter();
+        std::iter::once(0)
+            .chain(bounds.iter().copied())
+            .zip(bounds.iter().copied().chain(std::iter::once(nblk)))
+            .enumerate()
+            .map(|(stage, (start, end))| LLaMAStage {
+                stage,
+                n_stages,
+                offset: start,
+                embedding: embedding.take(),
+                blks: blks.by_ref().take(end - start).collect(),
+                output_head: if stage + 1 == n_stages {
+                    output_head.take()
+                } else {
+                    None
+                },
+            })
+            .collect()
+    }
+}
+
+impl<T> LLaMAStage<T> {
+    pub fn tensor_parallel(self, dist: Distribution) -> LLaMAStage<TPTensor<T>> {
+        self.parallel(dist, false, false)
+    }
+
+    pub fn sequence_parallel(self, dist: Distribution) -> LLaMAStage<TPTensor<T>> {
+        self.parallel(dist, true, false)
+    }
+
+    /// 见 [`LLaMA::vocab_parallel`]
+    pub fn vocab_parallel(self, dist: Distribution, sequence: bool) -> LLaMAStage<TPTensor<T>> {
+        self.parallel(dist, sequence, true)
+    }
+
+    fn parallel(self, dist: Distribution, sequence: bool, vocab: bool) -> LLaMAStage<TPTensor<T>> {
+        let Self {
+            stage,
+            n_stages,
+            offset,
+            embedding,
+            blks,
+            output_head,
+        } = self;
+        LLaMAStage {
+            stage,
+            n_stages,
+            offset,
+            embedding: embedding.map(|embedding| parallel_embedding(embedding, dist, vocab)),
+            blks: parallel_blks(blks, dist, sequence),
+            output_head: output_head.map(|head| parallel_head(head, dist, vocab)),
+        }
+    }
+}
+
+/// 嵌入和输出头默认不切分，`vocab` 为 `true` 时按词表切分
+fn parallel_embedding<T>(
+    embedding: Embedding<T>,
+    dist: Distribution,
+    vocab: bool,
+) -> Embedding<TPTensor<T>> {
+    if vocab {
+        embedding.vocab_parallel(dist)
+    } else {
+        embedding.tensor_parallel()
+    }
+}
+
+fn parallel_head<T>(
+    head: OutputHead<T>,
+    dist: Distribution,
+    vocab: bool,
+) -> OutputHead<TPTensor<T>> {
+    if vocab {
+        head.vocab_parallel(dist)
+    } else {
+        head.tensor_parallel()
+    }
+}
+
+fn parallel_blks<T>(
+    blks: Box<[TransformerBlk<T>]>,
+    dist: Distribution,
+    sequence: bool,
+) -> Box<[TransformerBlk<TPTensor<T>>]> {
+    blks.into_iter()
+        .map(|blk| {
+            if sequence {
+                blk.sequence_parallel(dist)
+            } else {
+                blk.tensor_parallel(dist)
+            }
+        })
+        .collect()
 }
 
 impl<T> NuralNetwork<T> for LLaMA<T> {
@@ -44,23 +193,267 @@ impl<T> NuralNetwork<T> for LLaMA<T> {
         let tokens = inputs.next().unwrap();
         let pos = inputs.next().unwrap();
 
+        let collective = collective(&blks);
         destruct!([x] = ctx.trap("embedding", embedding, [tokens])?);
+        let x = collective.scatter(&ctx, x)?;
+        let x = launch_blks(&mut ctx, blks, 0, x, pos)?;
+        let x = collective.gather(&mut ctx, x)?;
+        launch_head(ctx, output_head, x, inputs)
+    }
+}
 
-        let x = blks.into_iter().enumerate().try_fold(x, |x, (i, blk)| {
-            destruct!([x] = ctx.trap(format!("blk{i}"), blk, [x, pos.clone()])?);
-            Ok(x)
-        })?;
+impl<T> NuralNetwork<T> for LLaMAStage<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            stage,
+            n_stages,
+            offset,
+            embedding,
+            blks,
+            output_head,
+        } = self;
+        assert_eq!(embedding.is_some(), stage == 0);
 
-        let x = if let Some(OutputHead { out_norm, lm_head }) = output_head {
-            let out_idx = inputs.next().unwrap();
-            destruct!([x] = ctx.call("out-gather", "embedding", None, [x, out_idx])?);
-            destruct!([x] = ctx.trap("out-norm", out_norm, [x])?);
-            destruct!([x] = ctx.trap("lm-head", lm_head, [x])?);
-            x
+        let mut inputs = inputs.into_iter();
+        let x = inputs.next().unwrap();
+        let pos = inputs.next().unwrap();
+
+        let collective = collective(&blks);
+        let x = match embedding {
+            Some(embedding) => {
+                destruct!([x] = ctx.trap("embedding", embedding, [x])?);
+                collective.scatter(&ctx, x)?
+            }
+            None => {
+                let prev = Some(Arg::int(stage - 1));
+                destruct!([x] = ctx.call("recv", "recv", prev, [x])?);
+                x
+            }
+        };
+        let x = launch_blks(&mut ctx, blks, offset, x, pos)?;
+        if stage + 1 < n_stages {
+            let next = Some(Arg::int(stage + 1));
+            ctx.call("send", "send", next, [x])?;
+            Ok((ctx, vec![]))
         } else {
-            x
+            let x = collective.gather(&mut ctx, x)?;
+            launch_head(ctx, output_head, x, inputs)
+        }
+    }
+}
+
+/// 序列并行时块之间的激活只有本 rank 的 token，输出之前收集。
+/// 词表并行的嵌入需要所有 rank 查同样的 token，所以在嵌入之后切分
+fn collective<T>(blks: &[TransformerBlk<T>]) -> Collective {
+    blks.first().map_or(Collective::None, |blk| blk.collective)
+}
+
+/// 依次展开各块，块的命名从 `offset` 开始
+pub(super) fn launch_blks<T>(
+    ctx: &mut Context<T>,
+    blks: Box<[TransformerBlk<T>]>,
+    offset: usize,
+    x: Tensor<T>,
+    pos: Tensor<T>,
+) -> Result<Tensor<T>, NNError> {
+    blks.into_iter().enumerate().try_fold(x, |x, (i, blk)| {
+        destruct!([x] = ctx.trap(format!("blk{}", offset + i), blk, [x, pos.clone()])?);
+        Ok(x)
+    })
+}
+
+/// 选取输出行并展开输出头，没有输出头时直接输出隐状态
+pub(super) fn launch_head<T>(
+    ctx: Context<T>,
+    output_head: Option<OutputHead<T>>,
+    x: Tensor<T>,
+    mut inputs: impl Iterator<Item = Tensor<T>>,
+) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+    match output_head {
+        Some(output_head) => {
+            let out_idx = inputs.next().unwrap();
+            let x = x.index_select("out-gather", 0, out_idx)?;
+            // 输出头在当前命名空间中展开
+            output_head.launch([x], ctx)
+        }
+        None => Ok((ctx, vec![x])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Activation, Attention, Dim, Linear, Mlp, NormType, Normalization, Table,
+        ctx::{TensorMeta, test_builder},
+    };
+    use tensor::digit_layout::types;
+
+    fn norm(name: &str) -> Normalization<String> {
+        Normalization {
+            d: 32,
+            epsilon: 1e-5,
+            items: NormType::RmsNorm {
+                dt: types::F32,
+                scale: name.into(),
+                offset: 0.,
+            },
+        }
+    }
+
+    /// `d = 32`，4 个 q 头和 2 个 kv 头，词表大小为 10
+    fn llama(nblk: usize) -> LLaMA<String> {
+        let blk = |i: usize| {
+            let linear =
+                |name: &str, shape| Linear::new(types::F16, shape, format!("{i}.{name}"), None);
+            TransformerBlk::new(
+                norm(&format!("{i}.attn_norm")),
+                Attention {
+                    nh: 4,
+                    nkvh: 2,
+                    qkv: linear("qkv", [64, 32]),
+                    q_norm: None,
+                    k_norm: None,
+                    rope: None,
+                    causal: true,
+                    window: None,
+                    softcap: None,
+                    alibi: None,
+                    sinks: None,
+                    output: linear("output", [32, 32]),
+                },
+                norm(&format!("{i}.ffn_norm")),
+                Mlp {
+                    up: linear("ffn_up", [128, 32]),
+                    act: Activation::SwiGLU,
+                    down: linear("ffn_down", [32, 64]),
+                },
+            )
         };
+        LLaMA {
+            embedding: Embedding {
+                dt: types::F16,
+                d: 32,
+                wte: Table {
+                    row: 10,
+                    weight: "wte".into(),
+                },
+                wpe: None,
+                img_info: None,
+                scale: None,
+                vocab_parallel: None,
+            },
+            blks: (0..nblk).map(blk).collect(),
+            output_head: Some(OutputHead {
+                out_norm: norm("out_norm"),
+                lm_head: Linear::new(types::F16, [10, 32], "lm_head".into(), None),
+                softcap: None,
+                vocab_parallel: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn pipeline_tensor_parallel() {
+        const TP: usize = 2;
+        let n = || Dim::from("n");
+        let builder = test_builder();
+        let dists = Distribution::partition(&[1; TP]);
+        // 按全局 rank `stage * TP + r` 排列各阶段各分片的计算图
+        let graphs = llama(4)
+            .pipeline(&[2])
+            .into_iter()
+            .flat_map(|stage| {
+                let mut inputs = vec![
+                    if stage.stage == 0 {
+                        TensorMeta::new(types::U32, [n()])
+                    } else {
+                        TensorMeta::new(types::F16, [n(), 32.into()])
+                    },
+                    TensorMeta::new(types::U32, [n()]),
+                ];
+                if stage.output_head.is_some() {
+                    inputs.push(TensorMeta::new(types::U32, [Dim::from("m")]))
+                }
+                builder
+                    .build_parallel(&dists, |dist| stage.clone().tensor_parallel(dist), &inputs)
+                    .unwrap()
+            })
+            .map(|graph| graph.0)
+            .collect::<Vec<_>>();
+        assert_eq!(graphs.len(), 2 * TP);
 
-        Ok((ctx, vec![x]))
+        // 每个计算图中的 send/recv 节点，以及对方的全局 rank 和传递的隐状态
+        let p2p = graphs
+            .iter()
+            .enumerate()
+            .map(|(rank, graph)| {
+                let nodes = graph
+                    .topo
+                    .iter()
+                    .zip(&graph.nodes)
+                    .filter(|(_, node)| matches!(&*node.value.name, "send" | "recv"))
+                    .map(|(topo, node)| {
+                        let Some(Arg::Int(stage)) = node.value.arg else {
+                            panic!("{} requires peer stage", node.name)
+                        };
+                        let &[x] = topo.inputs else { panic!() };
+                        let peer = stage as usize * TP + rank % TP;
+                        (
+                            node.value.name.clone(),
+                            peer,
+                            graph.edges[x].meta.clone(),
+                            x,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                let [node] = <[_; 1]>::try_from(nodes).unwrap();
+                node
+            })
+            .collect::<Vec<_>>();
+
+        for (rank, graph) in graphs.iter().enumerate() {
+            let (op, peer, meta, x) = &p2p[rank];
+            let names = || graph.nodes.iter().map(|node| &*node.name);
+            // 每个阶段内部张量并行
+            assert!(
+                graph
+                    .nodes
+                    .iter()
+                    .any(|node| node.value.name == "all-reduce")
+            );
+            if rank < TP {
+                // 第一个阶段包含嵌入和前两个块，把隐状态发给下一个阶段中相同的分片
+                assert_eq!(op, "send");
+                assert_eq!(*peer, rank + TP);
+                assert!(graph.topo.global_outputs().is_empty());
+                assert!(names().any(|name| name.contains(".embedding")));
+                assert!(names().any(|name| name.contains(".blk1.")));
+                assert!(!names().any(|name| name.contains(".blk2.")))
+            } else {
+                // 第二个阶段从上一个阶段接收隐状态，从 blk2 开始命名，包含输出头
+                assert_eq!(op, "recv");
+                assert_eq!(*peer, rank - TP);
+                assert_eq!(*x, 0);
+                assert!(!names().any(|name| name.contains(".embedding")));
+                assert!(!names().any(|name| name.contains(".blk1.")));
+                assert!(names().any(|name| name.contains(".blk3.")));
+                let &[out] = graph.topo.global_outputs() else {
+                    panic!()
+                };
+                assert_eq!(&*graph.edges[out].meta.shape, [Dim::from("m"), 10.into()])
+            }
+            // 发送和接收成对，隐状态的形状一致
+            let (peer_op, peer_peer, peer_meta, _) = &p2p[*peer];
+            assert_ne!(op, peer_op);
+            assert_eq!(*peer_peer, rank);
+            assert_eq!(meta.dt, peer_meta.dt);
+            assert_eq!(meta.shape, peer_meta.shape);
+            assert_eq!(&*meta.shape, [Dim::from("n"), 32.into()])
+        }
     }
 }
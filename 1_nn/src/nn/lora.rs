@@ -0,0 +1,141 @@
+use super::{Context, NNError, TPAction, TPTensor, Tensor, macros::destruct};
+use crate::{Arg, Dim};
+use tensor::digit_layout::DigitLayout;
+
+/// 线性层的低秩适配器，输出增加 `scale * b · a · x`
+#[derive(Clone)]
+pub struct Lora<T> {
+    pub dt: DigitLayout,
+    pub rank: usize,
+    /// 通常为 `alpha / rank`
+    pub scale: f32,
+    /// 每个适配器的 `a: [rank, c]` 和 `b: [r, rank]`。
+    ///
+    /// 多于一个适配器时，线性层最后一个输入是每个 token 选择的适配器下标 `[m]`，
+    /// 不使用适配器的请求可以选择一个全零的适配器。
+    /// 注意力、MLP 等模块中的线性层没有这个输入，只能使用一个适配器
+    pub adapters: Vec<(T, T)>,
+}
+
+impl<T> Lora<T> {
+    #[inline]
+    pub fn is_multi(&self) -> bool {
+        self.adapters.len() > 1
+    }
+
+    /// 与基础权重一致地切分：行切分时切分 `a` 的列，列切分时按基础权重的方式切分 `b` 的行
+    pub(super) fn parallel(self, tp_action: Option<TPAction>, is_row: bool) -> Lora<TPTensor<T>> {
+        let Self {
+            dt,
+            rank,
+            scale,
+            adapters,
+        } = self;
+        let (act_a, act_b) = match tp_action {
+            Some(act) if is_row => (Some(act), None),
+            act => (None, act),
+        };
+        Lora {
+            dt,
+            rank,
+            scale,
+            adapters: adapters
+                .into_iter()
+                .map(|(a, b)| {
+                    let a = TPTensor {
+                        act: act_a.clone(),
+                        val: a,
+                    };
+                    let b = TPTensor {
+                        act: act_b.clone(),
+                        val: b,
+                    };
+                    (a, b)
+                })
+                .collect(),
+        }
+    }
+
+    /// 在 `y = linear(x)` 上加适配器的结果，`[r, c]` 为基础权重的形状
+    pub(super) fn launch(
+        self,
+        ctx: &mut Context<T>,
+        [r, c]: [usize; 2],
+        x: Tensor<T>,
+        y: Tensor<T>,
+        index: Option<Tensor<T>>,
+    ) -> Result<Tensor<T>, NNError> {
+        let multi = self.is_multi();
+        let Self {
+            dt,
+            rank,
+            scale,
+            adapters,
+        } = self;
+        let mut a = Vec::with_capacity(adapters.len());
+        let mut b = Vec::with_capacity(adapters.len());
+        for (i, (a_, b_)) in adapters.into_iter().enumerate() {
+            let prefix = if multi {
+                format!("lora.{i}")
+            } else {
+                "lora".into()
+            };
+            a.push(ctx.load_external(format!("{prefix}.a"), dt, [rank.into(), c.into()], a_));
+            b.push(ctx.load_external(format!("{prefix}.b"), dt, [r.into(), rank.into()], b_))
+        }
+
+        match index {
+            None => {
+                assert!(!multi, "multiple LoRA adapters require an adapter index");
+                // y + scale * (x · aᵀ) · bᵀ，加法融合到第二个线性层的残差
+                destruct!([a] = a);
+                destruct!([b] = b);
+                destruct!([h] = ctx.call("lora-a", "linear", Some(false.into()), [x, a])?);
+                let h = h.scale("lora-scale", scale as _)?;
+                destruct!([y] = ctx.call("lora-b", "linear", Some(true.into()), [h, y, b])?);
+                Ok(y)
+            }
+            Some(index) => {
+                // 堆叠为 [n_adapter, rank, c] 和 [n_adapter, r, rank]，每个 token 选择自己的适配器
+                let n = Dim::from(a.len());
+                destruct!([a] = ctx.call("lora-a-stack", "concat", Some(Arg::int(0)), a)?);
+                destruct!([b] = ctx.call("lora-b-stack", "concat", Some(Arg::int(0)), b)?);
+                let a = a.tile("", 0, [n.clone(), rank.into()])?;
+                let b = b.tile("", 0, [n, r.into()])?;
+                let a = a.index_select("lora-a-select", 0, index.clone())?;
+                let b = b.index_select("lora-b-select", 0, index)?;
+
+                // 激活在左侧，结果与 x 的数据类型相同
+                // [m, 1, c] x [m, c, rank] -> [m, 1, rank]
+                let x = x.tile("", 1, [Dim::from(1), c.into()])?;
+                let h = x.matmul("lora-a", a.transpose("", vec![0, 2, 1])?)?;
+                let h = h.scale("lora-scale", scale as _)?;
+                // [m, 1, rank] x [m, rank, r] -> [m, 1, r]
+                let d = h.matmul("lora-b", b.transpose("", vec![0, 2, 1])?)?;
+                let d = d.merge("", 1, 2)?;
+                y.add("lora-add", d)
+            }
+        }
+    }
+}
+
+/// 离线把一个适配器合并到基础权重，`w += scale * b · a`。
+///
+/// `w: [r, c]`、`a: [rank, c]` 和 `b: [r, rank]` 都是行优先连续存储的 `f32`，
+/// 其他数据类型由调用者转换后合并。
+pub fn merge_lora(w: &mut [f32], a: &[f32], b: &[f32], rank: usize, scale: f32) {
+    assert!(rank > 0 && a.len().is_multiple_of(rank));
+    let c = a.len() / rank;
+    assert!(b.len().is_multiple_of(rank));
+    let r = b.len() / rank;
+    assert_eq!(w.len(), r * c);
+
+    for (w, b) in w.chunks_exact_mut(c).zip(b.chunks_exact(rank)) {
+        for (a, &b) in a.chunks_exact(c).zip(b) {
+            let b = b * scale;
+            for (w, &a) in w.iter_mut().zip(a) {
+                *w += a * b
+            }
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use super::{
+    Context, Distribution, Linear, NNError, Normalization, NuralNetwork, RoPE, TPAction, TPTensor,
+    Tensor, macros::*,
+};
+use crate::weight_types::{ColumnTPWeight, RowTPWeight};
+use arg::{Arg, Dim};
+use tensor::digit_layout::types;
+
+/// DeepSeek-V2/V3 的多头潜在注意力。
+///
+/// q 和 kv 先压缩到低秩空间，每个头分为不带位置编码的 `dh_nope` 维和
+/// 所有头共享 k 的 `dh_rope` 维旋转部分，v 的头维度 `dv` 可以与 q·k 不同。
+#[derive(Clone)]
+pub struct MLAttention<T> {
+    pub nh: usize,
+    pub dh_nope: usize,
+    pub dh_rope: usize,
+    pub dv: usize,
+    pub dkv_lora: usize,
+    /// q 的低秩压缩 `[dq_lora, d]` 及其归一化，没有时 `q_b` 直接作用于输入
+    pub q_a: Option<(Linear<T>, Normalization<T>)>,
+    /// `[nh * (dh_nope + dh_rope), dq_lora 或 d]`
+    pub q_b: Linear<T>,
+    /// `[dkv_lora + dh_rope, d]`
+    pub kv_a: Linear<T>,
+    pub kv_a_norm: Normalization<T>,
+    /// `[nh * (dh_nope + dv), dkv_lora]`
+    pub kv_b: Linear<T>,
+    /// 旋转部分的位置编码，`dim` 为 `dh_rope`
+    pub rope: RoPE<T>,
+    /// 注意力分数的缩放，默认为 `1 / sqrt(dh_nope + dh_rope)`
+    pub scale: Option<f32>,
+    /// 使用吸收权重的推理形式，注意力直接作用于压缩的 kv
+    pub absorb: bool,
+    pub output: Linear<T>,
+}
+
+impl<T> MLAttention<T> {
+    pub fn tensor_parallel(self, dist: Distribution) -> MLAttention<TPTensor<T>> {
+        let Self {
+            nh,
+            dh_nope,
+            dh_rope,
+            dv,
+            dkv_lora,
+            q_a,
+            q_b,
+            kv_a,
+            kv_a_norm,
+            kv_b,
+            rope,
+            scale,
+            absorb,
+            output,
+        } = self;
+        let heads = dist.range(nh);
+        assert!(!heads.is_empty(), "cannot split {nh} heads into {dist:?}");
+        // 以头为单位重新表示切分方式，使分片边界落在头的边界上
+        let dist = Distribution::new(heads.start, heads.len(), nh);
+        // 压缩投影在各分片上重复计算，按头切分解压投影
+        let RoPE {
+            multimodal,
+            nctx,
+            dim,
+            sin,
+            cos,
+        } = rope;
+        MLAttention {
+            nh: heads.len(),
+            dh_nope,
+            dh_rope,
+            dv,
+            dkv_lora,
+            q_a: q_a.map(|(q_a, norm)| (q_a.into(), norm.tensor_parallel())),
+            q_b: q_b.parallel(TPAction::new(ColumnTPWeight, dist)),
+            kv_a: kv_a.into(),
+            kv_a_norm: kv_a_norm.tensor_parallel(),
+            kv_b: kv_b.parallel(TPAction::new(ColumnTPWeight, dist)),
+            rope: RoPE {
+                multimodal,
+                nctx,
+                dim,
+                sin: sin.into(),
+                cos: cos.into(),
+            },
+            scale,
+            absorb,
+            output: output.parallel(TPAction::new(RowTPWeight, dist)),
+        }
+    }
+}
+
+impl<T> NuralNetwork<T> for MLAttention<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        // 残差是可选的，没有时由外部相加
+        let mut inputs = inputs.into_iter();
+        let x = inputs.next().unwrap();
+        let pos = inputs.next().unwrap();
+        let residual = inputs.next();
+
+        let Self {
+            nh,
+            dh_nope,
+            dh_rope,
+            dv,
+            dkv_lora,
+            q_a,
+            q_b,
+            kv_a,
+            kv_a_norm,
+            kv_b,
+            rope,
+            scale,
+            absorb,
+            output,
+        } = self;
+        assert_eq!(rope.dim, dh_rope);
+        if let Some((q_a, _)) = &q_a {
+            q_a.check_adapter(&ctx, "attn-q-a")?
+        }
+        q_b.check_adapter(&ctx, "attn-q-b")?;
+        kv_a.check_adapter(&ctx, "attn-kv-a")?;
+        kv_b.check_adapter(&ctx, "attn-kv-b")?;
+        output.check_adapter(&ctx, "attn-output")?;
+
+        // q: [n, nh * (dh_nope + dh_rope)] -> [n, nh, dh_nope], [n, nh, dh_rope]
+        let q = match q_a {
+            Some((q_a, norm)) => {
+                destruct!([q] = ctx.trap("attn-q-a", q_a, [x.clone()])?);
+                destruct!([q] = ctx.trap("attn-q-a-norm", norm, [q])?);
+                q
+            }
+            None => x.clone(),
+        };
+        destruct!([q] = ctx.trap("attn-q-b", q_b, [q])?);
+        let q = q.tile("", 1, [nh.into(), Dim::from(dh_nope + dh_rope)])?;
+        destruct!([q_nope, q_pe] = q.split("split-q", 2, [dh_nope.into(), dh_rope.into()])?);
+
+        // kv: [n, dkv_lora + dh_rope] -> [n, dkv_lora], [n, dh_rope]
+        destruct!([kv] = ctx.trap("attn-kv-a", kv_a, [x])?);
+        destruct!([c_kv, k_pe] = kv.split("split-kv", 1, [dkv_lora.into(), dh_rope.into()])?);
+        destruct!([c_kv] = ctx.trap("attn-kv-a-norm", kv_a_norm, [c_kv])?);
+
+        let RoPE { nctx, sin, cos, .. } = rope;
+        let shape = [nctx.into(), Dim::from(dh_rope / 2)];
+        let sin = ctx.load_external("rope.sin", types::F32, shape.clone(), sin);
+        let cos = ctx.load_external("rope.cos", types::F32, shape, cos);
+        destruct!(
+            [q_pe] = ctx.call(
+                "attn-q-rope",
+                "rope",
+                None,
+                [q_pe, pos.clone(), sin.clone(), cos.clone()]
+            )?
+        );
+        destruct!([k_pe] = ctx.call("attn-k-rope", "rope", None, [k_pe, pos, sin, cos])?);
+
+        let scale = scale.unwrap_or(((dh_nope + dh_rope) as f32).sqrt().recip());
+        let arg = Some(Arg::dict([("scale".into(), Arg::float(scale as _))]));
+        let o = if absorb {
+            // 将 kv_b 拆为每个头的 w_uk: [nh, dh_nope, dkv_lora] 和 w_uv: [nh, dv, dkv_lora]
+            let Linear {
+                dt,
+                shape,
+                weight,
+                bias,
+                lora,
+                ..
+            } = kv_b;
+            assert!(bias.is_none(), "absorbed MLA does not support kv_b bias");
+            assert!(lora.is_none(), "absorbed MLA does not support kv_b LoRA");
+            let [r, c] = shape;
+            let w = ctx.load_external("attn-kv-b.weight", dt, [r.into(), c.into()], weight);
+            let w = w.tile("", 0, [nh.into(), Dim::from(dh_nope + dv)])?;
+            destruct!([w_uk, w_uv] = w.split("split-kv-b", 1, [dh_nope.into(), dv.into()])?);
+
+            // q_nope 吸收 w_uk: [nh, n, dh_nope] x [nh, dh_nope, dkv_lora]
+            let q_nope = q_nope.transpose("", vec![1, 0, 2])?;
+            let q_nope = q_nope.matmul("attn-absorb-q", w_uk)?;
+            let q_nope = q_nope.transpose("", vec![1, 0, 2])?;
+
+            destruct!([o] = ctx.call("", "mla", arg, [q_nope, q_pe, c_kv.clone(), k_pe, c_kv])?);
+
+            // 输出吸收 w_uv: [nh, n, dkv_lora] x [nh, dkv_lora, dv]
+            let o = o.transpose("", vec![1, 0, 2])?;
+            let o = o.matmul("attn-absorb-o", w_uv.transpose("", vec![0, 2, 1])?)?;
+            o.transpose("", vec![1, 0, 2])?
+        } else {
+            // kv: [n, nh * (dh_nope + dv)] -> [n, nh, dh_nope], [n, nh, dv]
+            destruct!([kv] = ctx.trap("attn-kv-b", kv_b, [c_kv])?);
+            let kv = kv.tile("", 1, [nh.into(), Dim::from(dh_nope + dv)])?;
+            destruct!([k_nope, v] = kv.split("split-kv-b", 2, [dh_nope.into(), dv.into()])?);
+
+            destruct!([o] = ctx.call("", "mla", arg, [q_nope, q_pe, k_nope, k_pe, v])?);
+            o
+        };
+
+        let o = o.merge("", 1, 2)?;
+        let outputs = ctx.trap("attn-output", output, std::iter::once(o).chain(residual));
+
+        Ok((ctx, outputs?))
+    }
+}
@@ -15,10 +15,10 @@ impl<T> Mlp<T> {
     pub fn tensor_parallel(self, dist: Distribution) -> Mlp<TPTensor<T>> {
         let Self { up, act, down } = self;
         Mlp {
-            up: up.parallel(match act {
-                Activation::SwiGLU => TPAction::new(FfnGateUp, dist),
-                Activation::SiLU => TPAction::new(FfnGateUp, dist),
-                Activation::GeLU => TPAction::new(ColumnTPWeight, dist),
+            up: up.parallel(if act.is_gated() {
+                TPAction::new(FfnGateUp, dist)
+            } else {
+                TPAction::new(ColumnTPWeight, dist)
             }),
             act,
             down: down.parallel(TPAction::new(RowTPWeight, dist)),
@@ -33,6 +33,8 @@ impl<T> NuralNetwork<T> for Mlp<T> {
         mut ctx: Context<T>,
     ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
         let Self { up, act, down } = self;
+        up.check_adapter(&ctx, "ffn-up")?;
+        down.check_adapter(&ctx, "ffn-down")?;
 
         let mut inputs = inputs.into_iter();
         let x = inputs.next().unwrap();
@@ -1,16 +1,24 @@
 ﻿mod activation;
 mod attention;
+mod bert;
 mod cogvlm;
+mod cross_attention;
 mod distribution;
 mod embedding;
+mod encoder_decoder;
+mod gpt2;
 mod linear;
 mod llama;
+mod lora;
 mod merger;
+mod mla;
 mod mlp;
 mod normalization;
 mod output_head;
 mod patch_embd;
+mod quant_linear;
 mod qw2vl_mmproj;
+mod rope;
 mod transformer_blk;
 
 use crate::{
@@ -20,19 +28,27 @@ use crate::{
 
 pub use activation::Activation;
 pub use attention::MRoPE;
-pub use attention::{Attention, RoPE};
+pub use attention::{Attention, RoPE, alibi_slopes};
+pub use bert::BertEncoder;
 pub use cogvlm::CogVLM;
+pub use cross_attention::CrossAttention;
 pub use distribution::{Distribution, TPAction, TPTensor, WeightType, weight_types};
 pub use embedding::{Embedding, Table};
+pub use encoder_decoder::{DecoderBlk, Encoder, EncoderDecoder};
+pub use gpt2::GPT2;
 pub use linear::Linear;
-pub use llama::LLaMA;
+pub use llama::{LLaMA, LLaMAStage};
+pub use lora::{Lora, merge_lora};
 pub use merger::Merger;
+pub use mla::MLAttention;
 pub use mlp::Mlp;
 pub use normalization::{Normalization, Type as NormType};
 pub use output_head::OutputHead;
 pub use patch_embd::PatchEmbd;
+pub use quant_linear::{QuantLinear, QuantScheme};
 pub use qw2vl_mmproj::Qwen2VLmmproj;
-pub use transformer_blk::TransformerBlk;
+pub use rope::{RopeScaling, RopeTable};
+pub use transformer_blk::{Collective, NormPlacement, ResidualScale, SelfAttn, TransformerBlk};
 
 pub trait NuralNetwork<T>: Sized {
     fn launch(
@@ -1,4 +1,5 @@
 ﻿use super::{Context, NNError, NuralNetwork, TPTensor, Tensor, macros::destruct};
+use arg::Arg;
 use tensor::digit_layout::DigitLayout;
 
 #[derive(Clone)]
@@ -13,6 +14,8 @@ pub enum Type<T> {
     RmsNorm {
         dt: DigitLayout,
         scale: T,
+        /// 缩放系数的偏移，Gemma 为 1，即按 `1 + scale` 缩放
+        offset: f32,
     },
     LayerNorm {
         dt_scale: DigitLayout,
@@ -29,9 +32,10 @@ impl<T> Normalization<T> {
             d,
             epsilon,
             items: match items {
-                Type::RmsNorm { dt, scale } => Type::RmsNorm {
+                Type::RmsNorm { dt, scale, offset } => Type::RmsNorm {
                     dt,
                     scale: scale.into(),
+                    offset,
                 },
                 Type::LayerNorm {
                     dt_scale,
@@ -59,9 +63,17 @@ impl<T> NuralNetwork<T> for Normalization<T> {
 
         let Self { d, epsilon, items } = self;
         let outputs = match items {
-            Type::RmsNorm { dt, scale } => {
+            Type::RmsNorm { dt, scale, offset } => {
                 let scale = ctx.load_external("scale", dt, [d.into()], scale);
-                ctx.call("", "rms-norm", Some(epsilon.into()), [x, scale])
+                let arg = if offset == 0. {
+                    epsilon.into()
+                } else {
+                    Arg::dict([
+                        ("epsilon".into(), epsilon.into()),
+                        ("offset".into(), Arg::float(offset as _)),
+                    ])
+                };
+                ctx.call("", "rms-norm", Some(arg), [x, scale])
             }
             Type::LayerNorm {
                 dt_scale,
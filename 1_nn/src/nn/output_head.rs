@@ -2,19 +2,51 @@
     Context, Distribution, Linear, NNError, Normalization, NuralNetwork, TPAction, TPTensor,
     Tensor, macros::destruct, weight_types::ColumnTPWeight,
 };
+use crate::Arg;
 
 #[derive(Clone)]
 pub struct OutputHead<T> {
     pub out_norm: Normalization<T>,
     pub lm_head: Linear<T>,
+    /// logits 的软截断系数
+    pub softcap: Option<f32>,
+    /// 词表并行时为完整的词表大小，每个 rank 只计算一部分词表的 logits，最后沿词表维度 all-gather
+    pub vocab_parallel: Option<usize>,
 }
 
 impl<T> OutputHead<T> {
     pub fn tensor_parallel(self) -> OutputHead<TPTensor<T>> {
-        let Self { out_norm, lm_head } = self;
+        let Self {
+            out_norm,
+            lm_head,
+            softcap,
+            ..
+        } = self;
         OutputHead {
             out_norm: out_norm.tensor_parallel(),
             lm_head: lm_head.parallel(TPAction::new(ColumnTPWeight, Distribution::MONO)),
+            softcap,
+            vocab_parallel: None,
+        }
+    }
+
+    /// 按行切分 `lm_head`，词表不能均分时各分片的行数不同，见 [`Distribution::range`]
+    pub fn vocab_parallel(self, dist: Distribution) -> OutputHead<TPTensor<T>> {
+        if dist.is_mono() {
+            return self.tensor_parallel();
+        }
+        let Self {
+            out_norm,
+            lm_head,
+            softcap,
+            ..
+        } = self;
+        let voc = lm_head.shape[0];
+        OutputHead {
+            out_norm: out_norm.tensor_parallel(),
+            lm_head: lm_head.parallel(TPAction::new(ColumnTPWeight, dist)),
+            softcap,
+            vocab_parallel: Some(voc),
         }
     }
 }
@@ -25,10 +57,74 @@ impl<T> NuralNetwork<T> for OutputHead<T> {
         inputs: impl IntoIterator<Item = Tensor<T>>,
         mut ctx: Context<T>,
     ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
-        let Self { out_norm, lm_head } = self;
+        let Self {
+            out_norm,
+            lm_head,
+            softcap,
+            vocab_parallel,
+        } = self;
+        lm_head.check_adapter(&ctx, "lm-head")?;
         destruct!([x] = inputs);
         destruct!([x] = ctx.trap("out-norm", out_norm, [x])?);
         destruct!([x] = ctx.trap("lm-head", lm_head, [x])?);
+        let x = match softcap {
+            Some(cap) => x.softcap("logits-softcap", cap as _)?,
+            None => x,
+        };
+        let x = match vocab_parallel {
+            Some(voc) => {
+                // 各 rank 的 logits 不一定等长，按完整的词表大小拼接
+                let arg = Arg::dict([("axis".into(), Arg::int(1)), ("len".into(), Arg::int(voc))]);
+                destruct!([x] = ctx.call("logits-gather", "all-gather", Some(arg), [x])?);
+                x
+            }
+            None => x,
+        };
         Ok((ctx, vec![x]))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Dim, NormType,
+        ctx::{TensorMeta, test_builder},
+    };
+    use tensor::digit_layout::types;
+
+    #[test]
+    fn uneven_vocab() {
+        let head = OutputHead {
+            out_norm: Normalization {
+                d: 32,
+                epsilon: 1e-5,
+                items: NormType::RmsNorm {
+                    dt: types::F32,
+                    scale: "out_norm".to_string(),
+                    offset: 0.,
+                },
+            },
+            lm_head: Linear::new(types::F16, [10, 32], "lm_head".into(), None),
+            softcap: None,
+            vocab_parallel: None,
+        };
+        let x = TensorMeta::new(types::F16, [Dim::from("n"), 32.into()]);
+        let builder = test_builder();
+        // 10 行的词表分为 3 份，各 rank 的 logits 不等长，拼接后是完整的词表
+        let graphs = builder
+            .build_parallel(
+                &Distribution::partition(&[1, 1, 1]),
+                |dist| head.clone().vocab_parallel(dist),
+                &[x],
+            )
+            .unwrap();
+        for graph in graphs {
+            let graph = graph.0;
+            let &[out] = graph.topo.global_outputs() else {
+                panic!()
+            };
+            assert_eq!(&*graph.edges[out].meta.shape, [Dim::from("n"), 10.into()])
+        }
+    }
+}
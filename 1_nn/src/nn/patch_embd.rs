@@ -1,6 +1,6 @@
 use super::{Context, Distribution, NNError, NuralNetwork, TPTensor, Tensor, macros::destruct};
 use crate::macros::dims;
-use arg::Dim;
+use arg::{Arg, Dim};
 use tensor::digit_layout::DigitLayout;
 
 #[derive(Clone)]
@@ -48,6 +48,11 @@ impl<T> NuralNetwork<T> for PatchEmbd<T> {
             patch_embd,
             patch_embd1,
         } = self;
+        // 以卷积核大小为步长，将图像切分为不重叠的块
+        let arg = Arg::dict([(
+            "stride".into(),
+            Arg::arr([shape[2], shape[3]].map(Arg::int)),
+        )]);
         let [m, ck, hk, wk] = shape.map(Dim::from);
         assert!(hk.eq(&wk));
         let w = ctx.load_external(
@@ -63,10 +68,10 @@ impl<T> NuralNetwork<T> for PatchEmbd<T> {
             patch_embd1,
         );
         let tensors = ctx
-            .call("", "conv", Some(false.into()), [x.clone(), w])
+            .call("", "conv", Some(arg.clone()), [x.clone(), w])
             .unwrap();
         destruct!([patch_embd] = tensors);
-        let tensors = ctx.call("", "conv", Some(false.into()), [x, w1]).unwrap();
+        let tensors = ctx.call("", "conv", Some(arg), [x, w1]).unwrap();
         destruct!([patch_embd1] = tensors);
         let tensors = ctx
             .call("", "add", None, [patch_embd, patch_embd1])
@@ -0,0 +1,156 @@
+use super::{Context, NNError, NuralNetwork, TPAction, TPTensor, Tensor, linear::split_shape};
+use arg::Arg;
+use std::iter::once;
+use tensor::digit_layout::{DigitLayout, types};
+
+/// 分组量化的线性层，权重与 [`Linear`](super::Linear) 一样按输出维度存储为 `[n, k]`。
+///
+/// `weight` 为 `U32[n, k / pack]`，每个 `U32` 从低位到高位打包 `pack = 32 / bits` 个权重；
+/// `scales` 为 `[n, k / group]`；`zeros` 为 `U32[n, k / group / pack]`，打包方式与权重相同。
+/// GPTQ/AWQ 的检查点需要先转换为这种布局，GGuf 的块量化类型直接使用 [`Linear`](super::Linear)。
+#[derive(Clone)]
+pub struct QuantLinear<T> {
+    pub shape: [usize; 2],
+    pub scheme: QuantScheme,
+    pub weight: T,
+    pub scales: (DigitLayout, T),
+    /// 零点，没有时为对称量化
+    pub zeros: Option<T>,
+    pub bias: Option<(DigitLayout, T)>,
+    pub allow_residual: bool,
+}
+
+/// 分组量化方式
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct QuantScheme {
+    /// 每个权重的位数，为 2、4 或 8
+    pub bits: usize,
+    /// 共享缩放和零点的权重数
+    pub group: usize,
+}
+
+impl QuantScheme {
+    /// 每个 `U32` 中打包的权重数
+    #[inline]
+    pub const fn pack(&self) -> usize {
+        32 / self.bits
+    }
+}
+
+impl<T> QuantLinear<T> {
+    pub fn new(
+        shape: [usize; 2],
+        scheme: QuantScheme,
+        weight: T,
+        scales: (DigitLayout, T),
+        zeros: Option<T>,
+        bias: Option<(DigitLayout, T)>,
+    ) -> Self {
+        let QuantScheme { bits, group } = scheme;
+        assert!(
+            matches!(bits, 2 | 4 | 8),
+            "unsupported quantization bits {bits}"
+        );
+        assert_eq!(group % scheme.pack(), 0);
+        assert_eq!(shape[1] % group, 0);
+        if zeros.is_some() {
+            assert_eq!(shape[1] / group % scheme.pack(), 0)
+        }
+        Self {
+            shape,
+            scheme,
+            weight,
+            scales,
+            zeros,
+            bias,
+            allow_residual: true,
+        }
+    }
+
+    pub fn parallel(self, tp_action: TPAction) -> QuantLinear<TPTensor<T>> {
+        let Self {
+            mut shape,
+            scheme,
+            weight,
+            scales,
+            zeros,
+            bias,
+            allow_residual,
+        } = self;
+        let (act, allow_residual, bias) = if !tp_action.dist.is_mono() {
+            let first = tp_action.dist.start == 0;
+            // 零点打包了多个组，行切分时不能拆开
+            let unit = match zeros {
+                Some(_) => scheme.group * scheme.pack(),
+                None => scheme.group,
+            };
+            let bias = if split_shape(&tp_action, &mut shape, unit) {
+                bias.filter(|_| first)
+            } else {
+                bias
+            };
+            (Some(tp_action), allow_residual && first, bias)
+        } else {
+            (None, allow_residual, bias)
+        };
+        let tp = |val| TPTensor {
+            act: act.clone(),
+            val,
+        };
+        QuantLinear {
+            shape,
+            scheme,
+            weight: tp(weight),
+            scales: (scales.0, tp(scales.1)),
+            zeros: zeros.map(tp),
+            bias: bias.map(|(dt, val)| (dt, tp(val))),
+            allow_residual,
+        }
+    }
+}
+
+impl<T> NuralNetwork<T> for QuantLinear<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        mut ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        let Self {
+            shape,
+            scheme,
+            weight,
+            scales,
+            zeros,
+            bias,
+            allow_residual,
+        } = self;
+        let [r, c] = shape;
+        let QuantScheme { bits, group } = scheme;
+        let pack = scheme.pack();
+
+        let w = ctx.load_external("weight", types::U32, [r.into(), (c / pack).into()], weight);
+        let (dt, scales) = scales;
+        let s = ctx.load_external("scales", dt, [r.into(), (c / group).into()], scales);
+        let z = zeros.map(|zeros| {
+            let shape = [r.into(), (c / group / pack).into()];
+            ctx.load_external("zeros", types::U32, shape, zeros)
+        });
+        let b = bias.map(|(dt, bias)| ctx.load_external("bias", dt, [r.into()], bias));
+
+        let mut inputs = inputs.into_iter();
+        let x = inputs.next().unwrap();
+        let residual = inputs.next().filter(|_| allow_residual);
+
+        let arg = Arg::dict([
+            ("residual".into(), Arg::bool(residual.is_some())),
+            ("bits".into(), Arg::int(bits)),
+            ("group".into(), Arg::int(group)),
+            ("zeros".into(), Arg::bool(z.is_some())),
+            ("bias".into(), Arg::bool(b.is_some())),
+        ]);
+        let inputs = once(x).chain(residual).chain([w, s]).chain(z).chain(b);
+        let outputs = ctx.call("", "quant-linear", Some(arg), inputs);
+
+        Ok((ctx, outputs?))
+    }
+}
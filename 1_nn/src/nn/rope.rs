@@ -0,0 +1,155 @@
+use std::f32::consts::PI;
+use tensor::{Tensor, digit_layout::types};
+
+/// rope 频率缩放方式
+#[derive(Clone, Copy, Default, Debug)]
+pub enum RopeScaling {
+    #[default]
+    None,
+    /// 位置插值，所有频率除以 `factor`
+    Linear { factor: f32 },
+    /// NTK-aware，将 theta 放大为 `theta * factor^(dim / (dim - 2))`
+    Ntk { factor: f32 },
+    /// 动态 NTK，表长度超过原始上下文长度时按超出的比例放大 theta
+    DynamicNtk { factor: f32, original_nctx: usize },
+    /// YaRN，按波长在插值和外推之间过渡，并缩放注意力
+    Yarn {
+        factor: f32,
+        original_nctx: usize,
+        beta_fast: f32,
+        beta_slow: f32,
+        attn_factor: f32,
+    },
+    /// Llama-3.1，低频插值、高频保持，中间平滑过渡
+    Llama3 {
+        factor: f32,
+        original_nctx: usize,
+        low_freq_factor: f32,
+        high_freq_factor: f32,
+    },
+}
+
+/// rope 的 sin/cos 表，形状为 `[nctx, dim / 2]`。
+///
+/// `dim` 是每个头中参与旋转的维度，小于头维度时只旋转每个头的前 `dim` 维。
+#[derive(Clone, Copy, Debug)]
+pub struct RopeTable {
+    pub nctx: usize,
+    pub dim: usize,
+    pub theta: f32,
+    pub scaling: RopeScaling,
+}
+
+impl RopeTable {
+    /// 缩放后每对维度的旋转频率
+    pub fn inv_freq(&self) -> Vec<f32> {
+        let &Self {
+            nctx,
+            dim,
+            theta,
+            scaling,
+        } = self;
+        assert_eq!(dim % 2, 0, "rope dim must be even");
+
+        let ntk = |factor: f32| theta * factor.powf(dim as f32 / (dim - 2) as f32);
+        let base = match scaling {
+            RopeScaling::Ntk { factor } => ntk(factor),
+            RopeScaling::DynamicNtk {
+                factor,
+                original_nctx,
+            } if nctx > original_nctx => {
+                ntk(factor * nctx as f32 / original_nctx as f32 - (factor - 1.))
+            }
+            _ => theta,
+        };
+        let freq = (0..dim / 2).map(|i| base.powf(-((2 * i) as f32 / dim as f32)));
+
+        match scaling {
+            RopeScaling::Linear { factor } => freq.map(|f| f / factor).collect(),
+            RopeScaling::Yarn {
+                factor,
+                original_nctx,
+                beta_fast,
+                beta_slow,
+                ..
+            } => {
+                // 旋转 n 圈对应的维度
+                let correction = |n: f32| {
+                    dim as f32 * (original_nctx as f32 / (n * 2. * PI)).ln() / (2. * base.ln())
+                };
+                let low = correction(beta_fast).floor().max(0.);
+                let high = correction(beta_slow).ceil().min((dim - 1) as f32);
+                let high = if low == high { high + 1e-3 } else { high };
+                freq.enumerate()
+                    .map(|(i, f)| {
+                        let ramp = ((i as f32 - low) / (high - low)).clamp(0., 1.);
+                        f / factor * ramp + f * (1. - ramp)
+                    })
+                    .collect()
+            }
+            RopeScaling::Llama3 {
+                factor,
+                original_nctx,
+                low_freq_factor,
+                high_freq_factor,
+            } => {
+                let low_freq_wavelen = original_nctx as f32 / low_freq_factor;
+                let high_freq_wavelen = original_nctx as f32 / high_freq_factor;
+                freq.map(|f| {
+                    let wavelen = 2. * PI / f;
+                    if wavelen < high_freq_wavelen {
+                        f
+                    } else if wavelen > low_freq_wavelen {
+                        f / factor
+                    } else {
+                        let smooth = (original_nctx as f32 / wavelen - low_freq_factor)
+                            / (high_freq_factor - low_freq_factor);
+                        (1. - smooth) * f / factor + smooth * f
+                    }
+                })
+                .collect()
+            }
+            RopeScaling::None | RopeScaling::Ntk { .. } | RopeScaling::DynamicNtk { .. } => {
+                freq.collect()
+            }
+        }
+    }
+
+    /// 乘在 sin/cos 上的系数，只有 YaRN 不为 1
+    pub fn mscale(&self) -> f32 {
+        match self.scaling {
+            RopeScaling::Yarn {
+                factor,
+                attn_factor,
+                ..
+            } if factor > 1. => attn_factor * (0.1 * factor.ln() + 1.),
+            RopeScaling::Yarn { attn_factor, .. } => attn_factor,
+            _ => 1.,
+        }
+    }
+
+    /// 生成 f32 的 sin 和 cos 表，可以直接作为常量外部张量加载
+    pub fn build<const N: usize>(&self) -> [Tensor<Box<[f32]>, N>; 2] {
+        let inv_freq = self.inv_freq();
+        let mscale = self.mscale();
+
+        let len = self.nctx * inv_freq.len();
+        let mut sin = Vec::with_capacity(len);
+        let mut cos = Vec::with_capacity(len);
+        for pos in 0..self.nctx {
+            for f in &inv_freq {
+                let (sin_, cos_) = (pos as f32 * f).sin_cos();
+                sin.push(sin_ * mscale);
+                cos.push(cos_ * mscale);
+            }
+        }
+
+        let tensor = |data: Vec<f32>| {
+            Tensor::from_dim_slice(types::F32, [self.nctx, inv_freq.len()]).map(|size| {
+                assert_eq!(size, data.len() * size_of::<f32>());
+                data.into_boxed_slice()
+            })
+        };
+        [tensor(sin), tensor(cos)]
+    }
+}
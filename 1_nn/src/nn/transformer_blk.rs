@@ -1,31 +1,146 @@
 ﻿use super::{
-    Attention, Context, Distribution, Mlp, NNError, Normalization, NuralNetwork, TPTensor, Tensor,
-    macros::destruct,
+    Attention, Context, Distribution, MLAttention, Mlp, NNError, Normalization, NuralNetwork,
+    OpError, TPTensor, Tensor, macros::destruct,
 };
+use crate::Arg;
+use arg::make_eq;
+use std::iter::once;
 
 #[derive(Clone)]
 pub struct TransformerBlk<T> {
     pub attn_norm: Normalization<T>,
-    pub attn: Attention<T>,
+    pub attn: SelfAttn<T>,
     pub ffn_norm: Normalization<T>,
     pub ffn: Mlp<T>,
-    pub all_reduce: bool,
+    pub norm: NormPlacement<T>,
+    /// 并行残差（GPT-J/Falcon/Phi），注意力和前馈都以块输入为输入，输出相加。
+    /// 只用一个归一化的模型 `ffn_norm` 与 `attn_norm` 相同
+    pub parallel: bool,
+    pub residual: ResidualScale,
+    pub collective: Collective,
+}
+
+/// 归一化在块中的位置，`f` 为注意力或前馈子层
+#[derive(Clone)]
+pub enum NormPlacement<T> {
+    /// `x + f(norm(x))`
+    Pre,
+    /// `norm(x + f(x))`，BERT
+    Post,
+    /// `x + norm(f(x))`，OLMo-2
+    Output,
+    /// `x + post(f(norm(x)))`，Gemma-2/3，`post` 为子层输出的额外归一化
+    Sandwich {
+        attn: Normalization<T>,
+        ffn: Normalization<T>,
+    },
+}
+
+/// 残差连接的缩放，子层的结果为 `x * residual + f(x) * branch`
+#[derive(Clone, Copy, Debug)]
+pub struct ResidualScale {
+    pub residual: f32,
+    pub branch: f32,
+}
+
+impl Default for ResidualScale {
+    fn default() -> Self {
+        Self {
+            residual: 1.,
+            branch: 1.,
+        }
+    }
+}
+
+impl ResidualScale {
+    fn is_identity(&self) -> bool {
+        self.residual == 1. && self.branch == 1.
+    }
+}
+
+/// 张量并行时子层的结果在各 rank 之间的合并方式
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Collective {
+    /// 没有切分，不需要通信
+    None,
+    /// 子层输出 all-reduce
+    AllReduce,
+    /// 序列并行，块的输入和输出按 token 均分到 `ranks` 个 rank 上，当前为第 `rank` 个。
+    ///
+    /// 子层的输入 all-gather，输出 reduce-scatter，归一化和残差只处理本 rank 的 token
+    SequenceParallel { rank: usize, ranks: usize },
+}
+
+impl Collective {
+    /// 取出本 rank 的 token，序列并行的块的输入。
+    ///
+    /// token 数必须能被 rank 数整除，为变量时约束随切分后的长度传递
+    pub(super) fn scatter<T>(&self, ctx: &Context<T>, x: Tensor<T>) -> Result<Tensor<T>, NNError> {
+        match *self {
+            Self::SequenceParallel { rank, ranks } => {
+                let n = &x.shape()[0];
+                let n_eq = make_eq(&[n, &(n.clone() / ranks * ranks)]).ok_or_else(|| NNError {
+                    name: format!("{}:seq-scatter", ctx.path()),
+                    err: OpError::ShapeMismatch,
+                })?;
+                let n = (n.clone() / ranks).with_constraints(&n_eq);
+                x.slice("seq-scatter", 0, n.clone() * rank, n, 1)
+            }
+            Self::None | Self::AllReduce => Ok(x),
+        }
+    }
+
+    /// 收集所有 rank 的 token
+    pub(super) fn gather<T>(
+        &self,
+        ctx: &mut Context<T>,
+        x: Tensor<T>,
+    ) -> Result<Tensor<T>, NNError> {
+        match *self {
+            Self::SequenceParallel { ranks, .. } => {
+                let arg = Arg::dict([("ranks".into(), Arg::int(ranks))]);
+                destruct!([x] = ctx.call("", "all-gather", Some(arg), [x])?);
+                Ok(x)
+            }
+            Self::None | Self::AllReduce => Ok(x),
+        }
+    }
+
+    /// 规约子层的部分和
+    fn reduce<T>(&self, ctx: &mut Context<T>, x: Tensor<T>) -> Result<Tensor<T>, NNError> {
+        let (op, arg) = match *self {
+            Self::None => return Ok(x),
+            Self::AllReduce => ("all-reduce", "sum".into()),
+            Self::SequenceParallel { ranks, .. } => (
+                "reduce-scatter",
+                Arg::dict([
+                    ("op".into(), "sum".into()),
+                    ("ranks".into(), Arg::int(ranks)),
+                ]),
+            ),
+        };
+        destruct!([x] = ctx.call("", op, Some(arg), [x])?);
+        Ok(x)
+    }
 }
 
 impl<T> TransformerBlk<T> {
     #[inline]
-    pub const fn new(
+    pub fn new(
         attn_norm: Normalization<T>,
-        attn: Attention<T>,
+        attn: impl Into<SelfAttn<T>>,
         ffn_norm: Normalization<T>,
         ffn: Mlp<T>,
     ) -> Self {
         Self {
             attn_norm,
-            attn,
+            attn: attn.into(),
             ffn_norm,
             ffn,
-            all_reduce: false,
+            norm: NormPlacement::Pre,
+            parallel: false,
+            residual: ResidualScale::default(),
+            collective: Collective::None,
         }
     }
 
@@ -35,6 +150,9 @@ impl<T> TransformerBlk<T> {
             attn,
             ffn_norm,
             ffn,
+            norm,
+            parallel,
+            residual,
             ..
         } = self;
         TransformerBlk {
@@ -42,7 +160,126 @@ impl<T> TransformerBlk<T> {
             attn: attn.tensor_parallel(dist),
             ffn_norm: ffn_norm.tensor_parallel(),
             ffn: ffn.tensor_parallel(dist),
-            all_reduce: !dist.is_mono(),
+            norm: norm.tensor_parallel(),
+            parallel,
+            residual,
+            collective: if dist.is_mono() {
+                Collective::None
+            } else {
+                Collective::AllReduce
+            },
+        }
+    }
+
+    /// 张量并行，并且在张量并行的 rank 之间按 token 切分激活，
+    /// 块的输入和输出只包含本 rank 的 token
+    pub fn sequence_parallel(self, dist: Distribution) -> TransformerBlk<TPTensor<T>> {
+        let Distribution { start, len, total } = dist;
+        let mut blk = self.tensor_parallel(dist);
+        if !dist.is_mono() {
+            assert!(
+                start % len == 0 && total % len == 0,
+                "sequence parallel requires evenly distributed ranks"
+            );
+            blk.collective = Collective::SequenceParallel {
+                rank: start / len,
+                ranks: total / len,
+            }
+        }
+        blk
+    }
+}
+
+impl<T> NormPlacement<T> {
+    pub fn tensor_parallel(self) -> NormPlacement<TPTensor<T>> {
+        match self {
+            Self::Pre => NormPlacement::Pre,
+            Self::Post => NormPlacement::Post,
+            Self::Output => NormPlacement::Output,
+            Self::Sandwich { attn, ffn } => NormPlacement::Sandwich {
+                attn: attn.tensor_parallel(),
+                ffn: ffn.tensor_parallel(),
+            },
+        }
+    }
+
+    /// 按位置确定一个子层的归一化，三明治归一化的输出归一化另外给出
+    pub(super) fn sub(&self, norm: Normalization<T>) -> SubNorms<T> {
+        let mut ans = SubNorms {
+            pre: None,
+            out: None,
+            post: None,
+        };
+        match self {
+            Self::Pre | Self::Sandwich { .. } => ans.pre = Some(norm),
+            Self::Post => ans.post = Some(norm),
+            Self::Output => ans.out = Some(norm),
+        }
+        ans
+    }
+
+    /// 将块的两个归一化按位置分配到注意力和前馈子层
+    pub(super) fn place(
+        self,
+        attn_norm: Normalization<T>,
+        ffn_norm: Normalization<T>,
+    ) -> [SubNorms<T>; 2] {
+        let mut ans = [self.sub(attn_norm), self.sub(ffn_norm)];
+        if let Self::Sandwich { attn, ffn } = self {
+            ans[0].out = Some(attn);
+            ans[1].out = Some(ffn)
+        }
+        ans
+    }
+}
+
+/// 一个子层周围的归一化
+pub(super) struct SubNorms<T> {
+    /// 子层输入的归一化
+    pre: Option<Normalization<T>>,
+    /// 子层输出在残差相加之前的归一化
+    out: Option<Normalization<T>>,
+    /// 残差相加之后的归一化
+    post: Option<Normalization<T>>,
+}
+
+/// 自注意力层的结构
+#[derive(Clone)]
+pub enum SelfAttn<T> {
+    MHA(Box<Attention<T>>),
+    MLA(Box<MLAttention<T>>),
+}
+
+impl<T> From<Attention<T>> for SelfAttn<T> {
+    fn from(value: Attention<T>) -> Self {
+        Self::MHA(Box::new(value))
+    }
+}
+
+impl<T> From<MLAttention<T>> for SelfAttn<T> {
+    fn from(value: MLAttention<T>) -> Self {
+        Self::MLA(Box::new(value))
+    }
+}
+
+impl<T> SelfAttn<T> {
+    pub fn tensor_parallel(self, dist: Distribution) -> SelfAttn<TPTensor<T>> {
+        match self {
+            Self::MHA(attn) => SelfAttn::MHA(Box::new((*attn).tensor_parallel(dist))),
+            Self::MLA(attn) => SelfAttn::MLA(Box::new((*attn).tensor_parallel(dist))),
+        }
+    }
+}
+
+impl<T> NuralNetwork<T> for SelfAttn<T> {
+    fn launch(
+        self,
+        inputs: impl IntoIterator<Item = Tensor<T>>,
+        ctx: Context<T>,
+    ) -> Result<(Context<T>, Vec<Tensor<T>>), NNError> {
+        match self {
+            Self::MHA(attn) => (*attn).launch(inputs, ctx),
+            Self::MLA(attn) => (*attn).launch(inputs, ctx),
         }
     }
 }
@@ -58,31 +295,145 @@ impl<T> NuralNetwork<T> for TransformerBlk<T> {
             attn,
             ffn_norm,
             ffn,
-            all_reduce,
+            norm,
+            parallel,
+            residual,
+            collective,
         } = self;
+        let [attn_norms, ffn_norms] = norm.place(attn_norm, ffn_norm);
 
         destruct!([x, pos] = inputs);
-        let residual = x.clone();
-        let tensors = ctx.trap("attn-norm", attn_norm, [x])?;
-        destruct!([x] = tensors);
-        let tensors = ctx.trap("attn", attn, [x, pos, residual])?;
-        let tensors = if all_reduce {
-            ctx.call("", "all-reduce", Some("sum".into()), tensors)?
+        let blk = Blk {
+            collective,
+            residual,
+        };
+        let x = if parallel {
+            blk.parallel(&mut ctx, x, pos, (attn, attn_norms), (ffn, ffn_norms))?
         } else {
-            tensors
+            let x = blk.sublayer(&mut ctx, "attn", attn, attn_norms, x, Some(pos))?;
+            blk.sublayer(&mut ctx, "ffn", ffn, ffn_norms, x, None)?
         };
 
-        destruct!([x] = tensors);
-        let residual = x.clone();
-        let tensors = ctx.trap("ffn-norm", ffn_norm, [x])?;
-        destruct!([x] = tensors);
-        let tensors = ctx.trap("ffn", ffn, [x, residual])?;
-        let tensors = if all_reduce {
-            ctx.call("", "all-reduce", Some("sum".into()), tensors)?
+        Ok((ctx, vec![x]))
+    }
+}
+
+/// 块中子层共享的连接方式
+pub(super) struct Blk {
+    pub collective: Collective,
+    pub residual: ResidualScale,
+}
+
+impl Blk {
+    /// 串行的子层，`pos` 只传给注意力，交叉注意力的 `pos` 是编码器输出
+    pub(super) fn sublayer<T>(
+        &self,
+        ctx: &mut Context<T>,
+        name: &str,
+        f: impl NuralNetwork<T>,
+        norms: SubNorms<T>,
+        x: Tensor<T>,
+        pos: Option<Tensor<T>>,
+    ) -> Result<Tensor<T>, NNError> {
+        let SubNorms { pre, out, post } = norms;
+        let fuse = out.is_none() && self.can_fuse();
+
+        let h = norm(ctx, format!("{name}-norm"), pre, x.clone())?;
+        let h = self.collective.gather(ctx, h)?;
+        let inputs = once(h).chain(pos).chain(fuse.then(|| x.clone()));
+        destruct!([y] = ctx.trap(name, f, inputs)?);
+        let y = self.collective.reduce(ctx, y)?;
+        let y = if fuse {
+            y
+        } else {
+            let y = norm(ctx, format!("{name}-out-norm"), out, y)?;
+            self.add_residual(name, x, y)?
+        };
+        norm(ctx, format!("{name}-post-norm"), post, y)
+    }
+
+    /// 并行的注意力和前馈，没有输出归一化时只需要规约一次
+    fn parallel<T>(
+        &self,
+        ctx: &mut Context<T>,
+        x: Tensor<T>,
+        pos: Tensor<T>,
+        (attn, attn_norms): (SelfAttn<T>, SubNorms<T>),
+        (ffn, ffn_norms): (Mlp<T>, SubNorms<T>),
+    ) -> Result<Tensor<T>, NNError> {
+        assert!(
+            attn_norms.post.is_none() && ffn_norms.post.is_none(),
+            "parallel residual does not support post-norm"
+        );
+        let fuse = attn_norms.out.is_none() && self.can_fuse();
+
+        let h = norm(ctx, "attn-norm".into(), attn_norms.pre, x.clone())?;
+        let h = self.collective.gather(ctx, h)?;
+        let inputs = [h, pos].into_iter().chain(fuse.then(|| x.clone()));
+        destruct!([a] = ctx.trap("attn", attn, inputs)?);
+        let h = norm(ctx, "ffn-norm".into(), ffn_norms.pre, x.clone())?;
+        let h = self.collective.gather(ctx, h)?;
+        destruct!([f] = ctx.trap("ffn", ffn, [h])?);
+
+        let y = match (attn_norms.out, ffn_norms.out) {
+            (None, None) => {
+                let y = a.add("parallel-add", f)?;
+                self.collective.reduce(ctx, y)?
+            }
+            (attn_out, ffn_out) => {
+                let a = self.collective.reduce(ctx, a)?;
+                let a = norm(ctx, "attn-out-norm".into(), attn_out, a)?;
+                let f = self.collective.reduce(ctx, f)?;
+                let f = norm(ctx, "ffn-out-norm".into(), ffn_out, f)?;
+                a.add("parallel-add", f)?
+            }
+        };
+        if fuse {
+            Ok(y)
         } else {
-            tensors
+            self.add_residual("parallel", x, y)
+        }
+    }
+
+    /// 残差可以融合到子层的输出投影中，规约后自然包含残差；
+    /// 序列并行时残差只有本 rank 的 token，不能融合
+    fn can_fuse(&self) -> bool {
+        self.residual.is_identity()
+            && !matches!(self.collective, Collective::SequenceParallel { .. })
+    }
+
+    fn add_residual<T>(
+        &self,
+        name: &str,
+        x: Tensor<T>,
+        y: Tensor<T>,
+    ) -> Result<Tensor<T>, NNError> {
+        let ResidualScale { residual, branch } = self.residual;
+        let x = if residual != 1. {
+            x.scale(format!("{name}-residual-scale"), residual as _)?
+        } else {
+            x
         };
+        let y = if branch != 1. {
+            y.scale(format!("{name}-branch-scale"), branch as _)?
+        } else {
+            y
+        };
+        x.add(format!("{name}-residual"), y)
+    }
+}
 
-        Ok((ctx, tensors))
+fn norm<T>(
+    ctx: &mut Context<T>,
+    name: String,
+    norm: Option<Normalization<T>>,
+    x: Tensor<T>,
+) -> Result<Tensor<T>, NNError> {
+    match norm {
+        Some(norm) => {
+            destruct!([x] = ctx.trap(name, norm, [x])?);
+            Ok(x)
+        }
+        None => Ok(x),
     }
 }
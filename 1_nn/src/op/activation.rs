@@ -2,52 +2,85 @@ use super::{OpError, Operator, macros::*};
 use crate::{Arg, TensorMeta};
 use arg::make_eq;
 
-pub struct SwiGLU;
+macro_rules! gated {
+    ($( $(#[$doc:meta])* $name:ident )+) => {
+        $(
+            $(#[$doc])*
+            pub struct $name;
 
-impl Operator for SwiGLU {
-    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
-        if args.is_some() {
-            return Err(OpError::ArgError);
-        }
+            impl Operator for $name {
+                fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+                    infer_gated(inputs, args)
+                }
+            }
+        )+
+    };
+}
 
-        destruct!([gate, up] = inputs);
+macro_rules! unary {
+    ($( $(#[$doc:meta])* $name:ident )+) => {
+        $(
+            $(#[$doc])*
+            pub struct $name;
 
-        dims!([_n, _d] = gate);
-        dims!([n_up, d_up] = up);
+            impl Operator for $name {
+                fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+                    infer_unary(inputs, args)
+                }
+            }
+        )+
+    };
+}
 
-        let n_up = make_eq(&[&gate.shape[0], n_up]).ok_or(OpError::ShapeMismatch)?;
-        let d_up = make_eq(&[&gate.shape[1], d_up]).ok_or(OpError::ShapeMismatch)?;
+gated! {
+    /// `silu(gate) * up`
+    SwiGLU
+    /// `gelu(gate) * up`
+    GeGLU
+    /// `relu(gate) * up`
+    ReGLU
+}
 
-        Ok(vec![TensorMeta::new(gate.dt, [n_up, d_up])])
-    }
+unary! {
+    /// `x * sigmoid(x)`
+    SiLU
+    /// 精确的 gelu
+    GeLU
+    /// tanh 近似的 gelu
+    GeLUTanh
+    /// CLIP 的 `x * sigmoid(1.702 * x)`
+    QuickGeLU
+    /// `max(x, 0)`
+    ReLU
+    /// `relu(x)^2`
+    SquaredReLU
+    Tanh
 }
 
-pub struct SiLU;
+/// 输入为形状相同的 `gate` 和 `up`
+fn infer_gated(inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+    if args.is_some() {
+        return Err(OpError::ArgError);
+    }
+
+    destruct!([gate, up] = inputs);
 
-impl Operator for SiLU {
-    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
-        if args.is_some() {
-            return Err(OpError::ArgError);
-        }
+    dims!([_n, _d] = gate);
+    dims!([n_up, d_up] = up);
 
-        destruct!([up] = inputs);
-        dims!([_n, _d] = up);
+    let n_up = make_eq(&[&gate.shape[0], n_up]).ok_or(OpError::ShapeMismatch)?;
+    let d_up = make_eq(&[&gate.shape[1], d_up]).ok_or(OpError::ShapeMismatch)?;
 
-        Ok(vec![up.clone()])
-    }
+    Ok(vec![TensorMeta::new(gate.dt, [n_up, d_up])])
 }
 
-pub struct GeLU;
-
-impl Operator for GeLU {
-    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
-        if args.is_some() {
-            return Err(OpError::ArgError);
-        }
+fn infer_unary(inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+    if args.is_some() {
+        return Err(OpError::ArgError);
+    }
 
-        destruct!([x] = inputs);
-        dims!([_n, _d] = x);
+    destruct!([x] = inputs);
+    dims!([_n, _d] = x);
 
-        Ok(vec![x.clone()])
-    }
+    Ok(vec![x.clone()])
 }
@@ -0,0 +1,57 @@
+use super::{OpError, Operator, macros::*};
+use crate::{Arg, TensorMeta};
+use std::collections::HashMap;
+
+/// 沿一个维度收集各 rank 的分片，按 rank 顺序拼接。
+///
+/// 参数为 `{"ranks": Int, "axis": Int}` 或 `{"len": Int, "axis": Int}`，`axis` 可省略，默认为 0。
+/// 各 rank 的分片等长时拼接结果的长度为分片长度乘 `ranks`；
+/// 不等长时由 `len` 指定拼接结果在 `axis` 上的长度，分片的长度为常数时不能超过 `len`。
+pub struct AllGather;
+
+impl Operator for AllGather {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(Arg::Dict(args)) = args else {
+            return Err(OpError::ArgError);
+        };
+        let axis = axis(args)?;
+
+        destruct!([x] = inputs);
+        let mut shape = x.shape().to_vec();
+        let Some(d) = shape.get_mut(axis) else {
+            return Err(OpError::ShapeError);
+        };
+        match args.get("len") {
+            Some(&Arg::Int(len)) if len > 0 => {
+                let len = len as usize;
+                if d.substitute(&HashMap::new()).is_some_and(|d| d > len) {
+                    return Err(OpError::ShapeMismatch);
+                }
+                *d = len.into()
+            }
+            Some(_) => return Err(OpError::ArgError),
+            None => *d = d.clone() * ranks(args)?,
+        }
+        Ok(vec![TensorMeta::new(x.dt, shape)])
+    }
+}
+
+/// 解析集合通信的参数 `{"ranks": Int, "axis": Int}`
+pub(super) fn ranks_axis(args: &HashMap<String, Arg>) -> Result<(usize, usize), OpError> {
+    Ok((ranks(args)?, axis(args)?))
+}
+
+fn ranks(args: &HashMap<String, Arg>) -> Result<usize, OpError> {
+    match args.get("ranks") {
+        Some(&Arg::Int(ranks)) if ranks > 0 => Ok(ranks as usize),
+        _ => Err(OpError::ArgError),
+    }
+}
+
+fn axis(args: &HashMap<String, Arg>) -> Result<usize, OpError> {
+    match args.get("axis") {
+        Some(&Arg::Int(axis)) => Ok(axis as usize),
+        Some(_) => Err(OpError::ArgError),
+        None => Ok(0),
+    }
+}
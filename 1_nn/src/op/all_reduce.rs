@@ -1,13 +1,17 @@
 ﻿use super::{OpError, Operator};
 use crate::{Arg, TensorMeta};
 
+/// 规约所有 rank 的输入，参数为规约方式 `Str`，可以是 `sum`、`prod`、`max`、`min`、`mean`。
 pub struct AllReduce;
 
 impl Operator for AllReduce {
     fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
-        let Some(&Arg::Str(_op)) = args else {
+        let Some(&Arg::Str(op)) = args else {
             return Err(OpError::ArgError);
         };
+        if !is_reduce_op(op) {
+            return Err(OpError::ArgError);
+        }
 
         match inputs {
             [x] => Ok(vec![x.clone()]),
@@ -15,3 +19,8 @@ impl Operator for AllReduce {
         }
     }
 }
+
+/// 集合通信支持的规约方式
+pub(super) fn is_reduce_op(op: &str) -> bool {
+    matches!(op, "sum" | "prod" | "max" | "min" | "mean")
+}
@@ -1,27 +1,213 @@
-use super::{OpError, Operator, macros::*};
-use crate::{Arg, TensorMeta};
+use super::{OpError, Operator};
+use crate::{Arg, Dim, TensorMeta};
 use arg::make_eq;
 
+/// 多头注意力。
+///
+/// 参数为字典：
+///
+/// - `dh`: `Dim`，头维度；
+/// - `nh`: `Int`，q 的头数；
+/// - `nkvh`: `Int`，k/v 的头数，必须整除 `nh`；
+/// - `causal`: `Bool`，可选，默认为 `true`，为 `false` 时每个 q 关注所有 k，k/v 的长度可以与 q 不同；
+/// - `window`: `Int`，可选，滑动窗口大小，每个 token 只关注最近的 `window` 个 token；
+/// - `softcap`: `Float`，可选，注意力分数软截断 `softcap * tanh(score / softcap)`；
+/// - `alibi`: `Bool`，可选，为 `true` 时输入 `slopes: [nh]` 作为 ALiBi 斜率；
+/// - `sinks`: `Bool`，可选，为 `true` 时输入 `sinks: [nh]` 作为每个头的注意力汇聚项；
+///
+/// 输入依次为 `q: [n, nh * dh]`、`k: [n_kv, nkvh * dh]`、`v: [n_kv, nkvh * dh]`，以及按上述顺序的可选输入，
+/// 输出为 `[n, nh * dh]`。
 pub struct Attention;
 
 impl Operator for Attention {
     fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
-        let Some(Arg::Dim(_dh)) = args else {
+        let Some(Arg::Dict(args)) = args else {
             return Err(OpError::ArgError);
         };
+        let Some(Arg::Dim(dh)) = args.get("dh") else {
+            return Err(OpError::ArgError);
+        };
+        let nh = positive(args.get("nh"))?;
+        let nkvh = positive(args.get("nkvh"))?;
+        if nh % nkvh != 0 {
+            return Err(OpError::ArgError);
+        }
+        if args.contains_key("window") {
+            positive(args.get("window"))?;
+        }
+        match args.get("softcap") {
+            Some(&Arg::Float(softcap)) if softcap.is_finite() && softcap > 0. => {}
+            Some(_) => return Err(OpError::ArgError),
+            None => {}
+        }
+        let flag = |key: &str, default: bool| match args.get(key) {
+            Some(&Arg::Bool(flag)) => Ok(flag),
+            Some(_) => Err(OpError::ArgError),
+            None => Ok(default),
+        };
+        let causal = flag("causal", true)?;
+        let alibi = flag("alibi", false)?;
+        let sinks = flag("sinks", false)?;
 
-        match inputs {
-            [q, k, v] => {
-                dims!([n_q, _dq] = q);
-                dims!([n_k, _dk] = k);
-                dims!([n_v, _dv] = v);
-
-                // Check if all inputs have the same batch size
-                let n_q = make_eq(&[&q.shape[0], n_q, n_k, n_v]).ok_or(OpError::ShapeMismatch)?;
+        let [q, k, v, extra @ ..] = inputs else {
+            return Err(OpError::ShapeError);
+        };
+        if extra.len() != alibi as usize + sinks as usize {
+            return Err(OpError::ShapeError);
+        }
+        let [n_q, dq] = q.shape() else {
+            return Err(OpError::ShapeError);
+        };
+        let [n_k, dk] = k.shape() else {
+            return Err(OpError::ShapeError);
+        };
+        let [n_v, dv] = v.shape() else {
+            return Err(OpError::ShapeError);
+        };
 
-                Ok(vec![TensorMeta::new(q.dt, [n_q, _dq.clone()])])
+        // 因果注意力的 q/k/v 长度相同，否则只要求 k/v 长度相同
+        let n_kv = make_eq(&[n_k, n_v]).ok_or(OpError::ShapeMismatch)?;
+        let n = if causal {
+            make_eq(&[n_q, &n_kv]).ok_or(OpError::ShapeMismatch)?
+        } else {
+            n_q.clone()
+        };
+        // 头数和头维度显式给出，检查与 q/k/v 的形状一致
+        let dq = make_eq(&[dq, &(dh.clone() * nh)]).ok_or(OpError::ShapeMismatch)?;
+        make_eq(&[dk, dv, &(dh.clone() * nkvh)]).ok_or(OpError::ShapeMismatch)?;
+        // 每个头一个斜率或汇聚项
+        for t in extra {
+            match t.shape() {
+                [d] if d.expr_eq(&Dim::from(nh)) => {}
+                [_] => return Err(OpError::ShapeMismatch),
+                _ => return Err(OpError::ShapeError),
             }
-            _ => Err(OpError::ShapeError),
         }
+
+        Ok(vec![TensorMeta::new(q.dt, [n, dq])])
+    }
+}
+
+fn positive(arg: Option<&Arg>) -> Result<usize, OpError> {
+    match arg {
+        Some(&Arg::Int(val)) if val > 0 => Ok(val as _),
+        _ => Err(OpError::ArgError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::op::test_utils::{meta, shape};
+    use tensor::digit_layout::types;
+
+    fn args(extra: impl IntoIterator<Item = (&'static str, Arg)>) -> Arg {
+        Arg::dict(
+            [
+                ("dh".into(), Arg::dim(64)),
+                ("nh".into(), Arg::int(8)),
+                ("nkvh".into(), Arg::int(2)),
+            ]
+            .into_iter()
+            .chain(extra.into_iter().map(|(k, v)| (k.into(), v))),
+        )
+    }
+
+    fn infer(inputs: &[&str], args: Arg) -> Result<TensorMeta, OpError> {
+        let inputs = inputs
+            .iter()
+            .map(|s| meta(types::F16, s))
+            .collect::<Vec<_>>();
+        Ok(Attention.infer(&inputs, Some(&args))?.pop().unwrap())
+    }
+
+    const QKV: [&str; 3] = ["n 512", "n 128", "n 128"];
+
+    #[test]
+    fn heads() {
+        let y = infer(&QKV, args([])).unwrap();
+        assert_eq!(y.dt, types::F16);
+        assert_eq!(&*y.shape, &*shape("n 512"));
+        // 头数与形状不符
+        assert!(matches!(
+            infer(&["n 512", "n 64", "n 64"], args([])),
+            Err(OpError::ShapeMismatch)
+        ));
+        // nkvh 必须整除 nh
+        assert!(matches!(
+            infer(&QKV, args([("nkvh", Arg::int(3))])),
+            Err(OpError::ArgError)
+        ));
+        assert!(matches!(
+            infer(&QKV, args([("nh", Arg::int(0))])),
+            Err(OpError::ArgError)
+        ))
+    }
+
+    #[test]
+    fn causal() {
+        // 非因果注意力的 q 与 k/v 长度可以不同
+        let kv = ["n 512", "s 128", "s 128"];
+        let y = infer(&kv, args([("causal", Arg::bool(false))])).unwrap();
+        assert_eq!(&*y.shape, &*shape("n 512"));
+        assert!(matches!(
+            infer(&["4 512", "6 128", "6 128"], args([])),
+            Err(OpError::ShapeMismatch)
+        ));
+        assert!(matches!(
+            infer(
+                &["4 512", "6 128", "5 128"],
+                args([("causal", Arg::bool(false))])
+            ),
+            Err(OpError::ShapeMismatch)
+        ))
+    }
+
+    #[test]
+    fn window_and_softcap() {
+        let extra = [("window", Arg::int(4096)), ("softcap", Arg::float(50.))];
+        assert!(infer(&QKV, args(extra)).is_ok());
+        for (key, arg) in [
+            ("window", Arg::int(0)),
+            ("window", Arg::float(1.)),
+            ("softcap", Arg::float(0.)),
+            ("softcap", Arg::float(f64::INFINITY)),
+            ("softcap", Arg::int(30)),
+        ] {
+            assert!(
+                matches!(infer(&QKV, args([(key, arg)])), Err(OpError::ArgError)),
+                "{key}"
+            )
+        }
+    }
+
+    #[test]
+    fn alibi_and_sinks() {
+        let both = || [("alibi", Arg::bool(true)), ("sinks", Arg::bool(true))];
+        let [q, k, v] = QKV;
+        assert!(infer(&[q, k, v, "8"], args([("alibi", Arg::bool(true))])).is_ok());
+        assert!(infer(&[q, k, v, "8", "8"], args(both())).is_ok());
+        // 可选输入数量必须与参数一致
+        assert!(matches!(
+            infer(&[q, k, v, "8"], args(both())),
+            Err(OpError::ShapeError)
+        ));
+        assert!(matches!(
+            infer(&[q, k, v, "8"], args([])),
+            Err(OpError::ShapeError)
+        ));
+        // 每个头一项
+        assert!(matches!(
+            infer(&[q, k, v, "8", "2"], args(both())),
+            Err(OpError::ShapeMismatch)
+        ));
+        assert!(matches!(
+            infer(&[q, k, v, "1 8"], args([("sinks", Arg::bool(true))])),
+            Err(OpError::ShapeError)
+        ));
+        assert!(matches!(
+            infer(&QKV, args([("alibi", Arg::int(1))])),
+            Err(OpError::ArgError)
+        ))
     }
 }
@@ -0,0 +1,104 @@
+use super::{OpError, Operator, macros::*};
+use crate::{Arg, Dim, TensorMeta};
+use arg::make_eq;
+
+/// 将张量按 NumPy 规则广播到目标形状，参数为 `{"shape": [Dim]}`
+pub struct Broadcast;
+
+impl Operator for Broadcast {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(Arg::Dict(args)) = args else {
+            return Err(OpError::ArgError);
+        };
+        let Some(Arg::Arr(shape)) = args.get("shape") else {
+            return Err(OpError::ArgError);
+        };
+
+        let mut shape = shape
+            .iter()
+            .map(|d| {
+                if let Arg::Dim(dim) = d {
+                    Ok(dim.clone())
+                } else {
+                    Err(OpError::ArgError)
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        destruct!([x] = inputs);
+
+        let x_shape = x.shape();
+        if x_shape.is_empty() || x_shape.len() > shape.len() {
+            return Err(OpError::ShapeError);
+        }
+
+        // 从最后一维开始对齐，输入的每一维为 1 或与目标相等
+        let offset = shape.len() - x_shape.len();
+        let one = Dim::from(1);
+        for (d, target) in x_shape.iter().zip(&mut shape[offset..]) {
+            if !d.expr_eq(&one) {
+                *target = make_eq(&[target, d]).ok_or(OpError::ShapeMismatch)?
+            }
+        }
+
+        Ok(vec![TensorMeta::new(x.dt, shape)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::op::test_utils::{meta, shape};
+    use std::collections::HashMap;
+    use tensor::digit_layout::types;
+
+    fn broadcast(x: &str, target: &str) -> Result<Vec<TensorMeta>, OpError> {
+        let arg = Arg::dict([(
+            "shape".into(),
+            Arg::arr(shape(target).into_iter().map(Arg::from)),
+        )]);
+        Broadcast.infer(&[meta(types::F32, x)], Some(&arg))
+    }
+
+    #[test]
+    fn compatible() {
+        for (x, target) in [
+            ("2 3 4", "2 3 4"),
+            ("3 1", "2 3 4"),
+            ("1", "n 4"),
+            ("4", "n 4"),
+        ] {
+            let [y] = &*broadcast(x, target).unwrap() else {
+                panic!()
+            };
+            assert_eq!(&*y.shape, &*shape(target), "{x} -> {target}")
+        }
+        // 符号维度与目标建立相等约束
+        let [y] = &*broadcast("n 1", "m 4").unwrap() else {
+            panic!()
+        };
+        let [d, _] = &*y.shape else { panic!() };
+        assert_eq!(d.substitute(&HashMap::from([("n", 3), ("m", 3)])), Some(3));
+        assert_eq!(d.substitute(&HashMap::from([("n", 3), ("m", 2)])), None)
+    }
+
+    #[test]
+    fn incompatible() {
+        // 不是 1 的维度与目标不等
+        assert!(matches!(
+            broadcast("2 3", "3 3"),
+            Err(OpError::ShapeMismatch)
+        ));
+        // 输入的维数多于目标
+        assert!(matches!(
+            broadcast("2 3 4", "3 4"),
+            Err(OpError::ShapeError)
+        ));
+        // 目标形状必须是维度
+        let arg = Arg::dict([("shape".into(), Arg::arr([Arg::bool(true)]))]);
+        assert!(matches!(
+            Broadcast.infer(&[meta(types::F32, "1")], Some(&arg)),
+            Err(OpError::ArgError)
+        ))
+    }
+}
@@ -1,54 +1,230 @@
-use super::{OpError, Operator, macros::*};
+use super::{OpError, Operator};
 use crate::{Arg, TensorMeta};
 use arg::make_eq;
+use std::collections::{BTreeSet, HashMap};
 
+/// 1D/2D/3D 卷积。
+///
+/// 输入为 `x: [n, c, *spatial]`、`w: [m, c / groups, *kernel]`，可选 `b: [m]`。
+/// 参数为字典，各项均可省略：
+///
+/// - `stride`: `[Int; ndim]`，默认全为 1；
+/// - `padding`: `[Int; ndim]`，两侧对称填充，默认全为 0；
+/// - `dilation`: `[Int; ndim]`，默认全为 1；
+/// - `groups`: `Int`，默认为 1；
+///
+/// 每个空间维度的输出为 `(x + 2*padding - dilation*(kernel - 1) - 1) / stride + 1`（向下取整），
+/// 空间维度为常数时要求输出为正。
 pub struct Conv;
 
 impl Operator for Conv {
     fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
-        let Some(Arg::Bool(bias)) = args else {
-            return Err(OpError::ArgError);
+        let args = match args {
+            Some(Arg::Dict(args)) => Some(args),
+            None => None,
+            Some(_) => return Err(OpError::ArgError),
+        };
+
+        let (x, w, b) = match inputs {
+            [x, w] => (x, w, None),
+            [x, w, b] => (x, w, Some(b)),
+            _ => return Err(OpError::ShapeError),
+        };
+
+        let [n, c, spatial @ ..] = x.shape() else {
+            return Err(OpError::ShapeError);
+        };
+        let [m, ck, kernel @ ..] = w.shape() else {
+            return Err(OpError::ShapeError);
+        };
+        let ndim = spatial.len();
+        if !(1..=3).contains(&ndim) || kernel.len() != ndim {
+            return Err(OpError::ShapeError);
+        }
+
+        let stride = int_arr(args, "stride", ndim, 1)?;
+        let padding = int_arr(args, "padding", ndim, 0)?;
+        let dilation = int_arr(args, "dilation", ndim, 1)?;
+        let groups = match args.and_then(|args| args.get("groups")) {
+            Some(&Arg::Int(groups)) if groups > 0 => groups as usize,
+            Some(_) => return Err(OpError::ArgError),
+            None => 1,
         };
-        match inputs {
-            [x, w] if !*bias => {
-                dims!([n, c, height, width] = x);
-                dims!([m, ck, hk, wk] = w);
-
-                // Check if channel match
-                if c != ck {
-                    return Err(OpError::ShapeMismatch);
-                }
-
-                let ny = n.clone();
-                let my = m.clone();
-                let hy = height.clone() / hk.clone();
-                let wy = width.clone() / wk.clone();
-
-                Ok(vec![TensorMeta::new(x.dt, [ny, my, hy, wy])])
+        if stride.contains(&0) || dilation.contains(&0) {
+            return Err(OpError::ArgError);
+        }
+
+        // 输入通道数等于每组通道数乘以组数，输出通道数能被组数整除
+        let c = make_eq(&[c, &(ck.clone() * groups)]).ok_or(OpError::ShapeMismatch)?;
+        let m = make_eq(&[m, &(m.clone() / groups * groups)]).ok_or(OpError::ShapeMismatch)?;
+        // 通道数不出现在输出中，其约束随输出通道数传递
+        let m = m.with_constraints(&c);
+        let m = match b {
+            Some(b) => {
+                let [mb] = b.shape() else {
+                    return Err(OpError::ShapeError);
+                };
+                make_eq(&[&m, mb]).ok_or(OpError::ShapeMismatch)?
             }
-            [x, w, b] if *bias => {
-                dims!([n, c, height, width] = x);
-                dims!([m, ck, hk, wk] = w);
-                dims!([mb] = b);
-
-                // Check if channel match
-                if c != ck {
-                    return Err(OpError::ShapeMismatch);
-                }
-
-                // Check if embd_dims match
-                if m != mb {
-                    return Err(OpError::ShapeMismatch);
-                }
-
-                let ny = n.clone();
-                let my = make_eq(&[m, mb]).ok_or(OpError::ShapeMismatch)?;
-                let hy = height.clone() / hk.clone();
-                let wy = width.clone() / wk.clone();
-
-                Ok(vec![TensorMeta::new(x.dt, [ny, my, hy, wy])])
+            None => m,
+        };
+
+        let mut shape = vec![n.clone(), m];
+        for i in 0..ndim {
+            let extent = (kernel[i].clone() - 1) * dilation[i] + 1;
+            let padded = spatial[i].clone() + 2 * padding[i];
+            let out = (padded - extent) / stride[i] + 1;
+            let mut vars = BTreeSet::new();
+            out.append_variables(&mut vars);
+            if vars.is_empty() && out.substitute(&HashMap::new()).is_none_or(|d| d == 0) {
+                return Err(OpError::ShapeError);
             }
-            _ => Err(OpError::ShapeError),
+            shape.push(out)
+        }
+
+        Ok(vec![TensorMeta::new(x.dt, shape)])
+    }
+}
+
+fn int_arr(
+    args: Option<&HashMap<String, Arg>>,
+    key: &str,
+    ndim: usize,
+    default: usize,
+) -> Result<Vec<usize>, OpError> {
+    match args.and_then(|args| args.get(key)) {
+        Some(Arg::Arr(arr)) if arr.len() == ndim => arr
+            .iter()
+            .map(|a| match a {
+                &Arg::Int(val) => Ok(val as usize),
+                _ => Err(OpError::ArgError),
+            })
+            .collect(),
+        Some(_) => Err(OpError::ArgError),
+        None => Ok(vec![default; ndim]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::op::test_utils::{meta, shape};
+    use tensor::digit_layout::types;
+
+    fn arg(stride: &[usize], padding: &[usize], dilation: &[usize], groups: usize) -> Arg {
+        let arr = |v: &[usize]| Arg::arr(v.iter().map(|&v| Arg::int(v)));
+        Arg::dict([
+            ("stride".into(), arr(stride)),
+            ("padding".into(), arr(padding)),
+            ("dilation".into(), arr(dilation)),
+            ("groups".into(), Arg::int(groups)),
+        ])
+    }
+
+    fn infer(inputs: &[&str], arg: Option<Arg>) -> Result<TensorMeta, OpError> {
+        let inputs = inputs
+            .iter()
+            .map(|s| meta(types::F32, s))
+            .collect::<Vec<_>>();
+        let mut outputs = Conv.infer(&inputs, arg.as_ref())?;
+        assert_eq!(outputs.len(), 1);
+        Ok(outputs.pop().unwrap())
+    }
+
+    #[test]
+    fn output_size() {
+        let cases = [
+            // 默认参数
+            (["1 2 4 5 6", "4 2 2 2 2"], None, "1 4 3 4 5"),
+            // 步长
+            (["2 4 10", "6 4 3"], Some(arg(&[3], &[0], &[1], 1)), "2 6 3"),
+            // 步长和填充，向下取整
+            (
+                ["1 3 32 31", "8 3 3 3"],
+                Some(arg(&[2, 2], &[1, 1], &[1, 1], 1)),
+                "1 8 16 16",
+            ),
+            // 空洞
+            (
+                ["1 3 32 32", "8 3 3 3"],
+                Some(arg(&[1, 1], &[2, 0], &[2, 1], 1)),
+                "1 8 32 30",
+            ),
+            // 分组
+            (["1 8 10", "4 2 3"], Some(arg(&[1], &[0], &[1], 4)), "1 4 8"),
+        ];
+        for (inputs, arg, expected) in cases {
+            let y = infer(&inputs, arg).unwrap();
+            assert_eq!(&*y.shape, &*shape(expected), "{inputs:?}")
+        }
+    }
+
+    #[test]
+    fn symbolic() {
+        let y = infer(
+            &["n 3 h w", "8 3 3 3"],
+            Some(arg(&[2, 1], &[1, 1], &[1, 2], 1)),
+        )
+        .unwrap();
+        let [n, m, h, w] = &*y.shape else { panic!() };
+        assert_eq!(n.to_string(), "n");
+        assert_eq!(m.to_usize(), 8);
+        for (x, h_, w_) in [(7, 4, 5), (32, 16, 30), (33, 17, 31)] {
+            let value = HashMap::from([("h", x), ("w", x)]);
+            assert_eq!(h.substitute(&value), Some(h_));
+            assert_eq!(w.substitute(&value), Some(w_))
         }
     }
+
+    #[test]
+    fn bias() {
+        let arg = || Some(arg(&[1], &[0], &[1], 1));
+        let y = infer(&["1 2 5", "4 2 3", "4"], arg()).unwrap();
+        assert_eq!(&*y.shape, &*shape("1 4 3"));
+        assert!(matches!(
+            infer(&["1 2 5", "4 2 3", "5"], arg()),
+            Err(OpError::ShapeMismatch)
+        ))
+    }
+
+    #[test]
+    fn invalid() {
+        // 卷积核大于填充后的输入
+        assert!(matches!(
+            infer(&["1 1 2", "1 1 5"], None),
+            Err(OpError::ShapeError)
+        ));
+        // 输入通道数不是每组通道数乘组数，输出通道数不能被组数整除
+        let groups = |g| Some(arg(&[1], &[0], &[1], g));
+        assert!(matches!(
+            infer(&["1 8 10", "4 2 3"], groups(3)),
+            Err(OpError::ShapeMismatch)
+        ));
+        assert!(matches!(
+            infer(&["1 8 10", "6 2 3"], groups(4)),
+            Err(OpError::ShapeMismatch)
+        ));
+        // 步长或空洞为 0，参数与空间维数不符
+        assert!(matches!(
+            infer(&["1 1 5", "1 1 3"], Some(arg(&[0], &[0], &[1], 1))),
+            Err(OpError::ArgError)
+        ));
+        assert!(matches!(
+            infer(&["1 1 5", "1 1 3"], Some(arg(&[1], &[0], &[0], 1))),
+            Err(OpError::ArgError)
+        ));
+        assert!(matches!(
+            infer(&["1 1 5 5", "1 1 3 3"], Some(arg(&[1], &[0], &[1], 1))),
+            Err(OpError::ArgError)
+        ));
+        // 没有空间维度，或卷积核维数不符
+        assert!(matches!(
+            infer(&["1 1", "1 1"], None),
+            Err(OpError::ShapeError)
+        ));
+        assert!(matches!(
+            infer(&["1 1 5 5", "1 1 3"], None),
+            Err(OpError::ShapeError)
+        ))
+    }
 }
@@ -0,0 +1,201 @@
+use super::{OpError, Operator, macros::*};
+use crate::{Arg, Dim, TensorMeta};
+use arg::make_eq;
+use tensor::digit_layout::{DigitLayout, types};
+
+macro_rules! binary {
+    ($( $(#[$doc:meta])* $name:ident )+) => {
+        $(
+            $(#[$doc])*
+            pub struct $name;
+
+            impl Operator for $name {
+                fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+                    infer_binary(inputs, args)
+                }
+            }
+        )+
+    };
+}
+
+binary! {
+    /// 逐元素加，输入按 NumPy 规则广播
+    Add
+    /// 逐元素减，输入按 NumPy 规则广播
+    Sub
+    /// 逐元素乘，输入按 NumPy 规则广播
+    Mul
+    /// 逐元素除，输入按 NumPy 规则广播
+    Div
+}
+
+fn infer_binary(inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+    if args.is_some() {
+        return Err(OpError::ArgError);
+    }
+
+    destruct!([a, b] = inputs);
+    if a.dt != b.dt {
+        return Err(OpError::DataTypeMismatch);
+    }
+
+    let shape = broadcast_shape(a.shape(), b.shape()).ok_or(OpError::ShapeMismatch)?;
+    Ok(vec![TensorMeta::new(a.dt, shape)])
+}
+
+/// 乘以标量，参数为 `Float`
+pub struct Scale;
+
+impl Operator for Scale {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(Arg::Float(_)) = args else {
+            return Err(OpError::ArgError);
+        };
+
+        destruct!([x] = inputs);
+        Ok(vec![x.clone()])
+    }
+}
+
+/// 软截断 `cap * tanh(x / cap)`，参数为正的 `Float` cap
+pub struct Softcap;
+
+impl Operator for Softcap {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(&Arg::Float(cap)) = args else {
+            return Err(OpError::ArgError);
+        };
+        if cap <= 0. {
+            return Err(OpError::ArgError);
+        }
+
+        destruct!([x] = inputs);
+        Ok(vec![x.clone()])
+    }
+}
+
+/// 类型转换，参数为目标类型的名字，见 [`dt_name`]
+pub struct Cast;
+
+impl Operator for Cast {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(&Arg::Str(name)) = args else {
+            return Err(OpError::ArgError);
+        };
+        let dt = dt_from_name(name).ok_or(OpError::ArgError)?;
+
+        destruct!([x] = inputs);
+        if x.dt.group_size() > 1 || dt.group_size() > 1 {
+            return Err(OpError::DataTypeError);
+        }
+        Ok(vec![TensorMeta::new(dt, x.shape().to_vec())])
+    }
+}
+
+/// 按 NumPy 规则计算两个形状广播后的形状。
+///
+/// 从最后一维开始对齐，长度为 1 的维度扩展为另一方的长度；
+/// 两个符号维度按相等处理并附加相等约束，可以判定不相等时返回 `None`。
+pub fn broadcast_shape(a: &[Dim], b: &[Dim]) -> Option<Vec<Dim>> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let offset = long.len() - short.len();
+    let one = Dim::from(1);
+
+    let mut ans = long[..offset].to_vec();
+    for (l, s) in long[offset..].iter().zip(short) {
+        ans.push(if s.expr_eq(&one) {
+            l.clone()
+        } else if l.expr_eq(&one) {
+            s.clone()
+        } else {
+            make_eq(&[l, s])?
+        })
+    }
+    Some(ans)
+}
+
+const DT_NAMES: [(&str, DigitLayout); 12] = [
+    ("u8", types::U8),
+    ("u16", types::U16),
+    ("u32", types::U32),
+    ("u64", types::U64),
+    ("i8", types::I8),
+    ("i16", types::I16),
+    ("i32", types::I32),
+    ("i64", types::I64),
+    ("f16", types::F16),
+    ("bf16", types::BF16),
+    ("f32", types::F32),
+    ("f64", types::F64),
+];
+
+/// 可以作为 `cast` 目标的数据类型名字
+pub fn dt_name(dt: DigitLayout) -> Option<&'static str> {
+    DT_NAMES
+        .iter()
+        .find(|(_, dt_)| *dt_ == dt)
+        .map(|(name, _)| *name)
+}
+
+fn dt_from_name(name: &str) -> Option<DigitLayout> {
+    DT_NAMES
+        .iter()
+        .find(|(name_, _)| *name_ == name)
+        .map(|(_, dt)| *dt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::op::test_utils::{meta, shape};
+    use std::collections::HashMap;
+
+    fn add(a: &str, b: &str) -> Result<Vec<TensorMeta>, OpError> {
+        Add.infer(&[meta(types::F32, a), meta(types::F32, b)], None)
+    }
+
+    #[test]
+    fn broadcast() {
+        let cases = [
+            ("2 3 4", "2 3 4", "2 3 4"),
+            ("2 1 4", "3 1", "2 3 4"),
+            ("4", "2 3 4", "2 3 4"),
+            ("1", "n 4", "n 4"),
+            ("n 1", "1 m", "n m"),
+        ];
+        for (a, b, expected) in cases {
+            let [y] = &*add(a, b).unwrap() else { panic!() };
+            assert_eq!(&*y.shape, &*shape(expected), "{a} + {b}");
+            // 广播与操作数的顺序无关
+            let [y] = &*add(b, a).unwrap() else { panic!() };
+            assert_eq!(&*y.shape, &*shape(expected), "{b} + {a}")
+        }
+    }
+
+    #[test]
+    fn symbolic() {
+        // 两个变量按相等处理，代入的值不等时约束不成立
+        let [y] = &*add("n 4", "m 1").unwrap() else {
+            panic!()
+        };
+        let [d, _] = &*y.shape else { panic!() };
+        assert_eq!(d.substitute(&HashMap::from([("n", 2), ("m", 2)])), Some(2));
+        assert_eq!(d.substitute(&HashMap::from([("n", 2), ("m", 3)])), None)
+    }
+
+    #[test]
+    fn incompatible() {
+        assert!(matches!(add("2 3", "4 3"), Err(OpError::ShapeMismatch)));
+        assert!(matches!(
+            Add.infer(&[meta(types::F32, "3"), meta(types::F16, "3")], None),
+            Err(OpError::DataTypeMismatch)
+        ));
+        assert!(matches!(
+            Mul.infer(
+                &[meta(types::F32, "3"), meta(types::F32, "3")],
+                Some(&Arg::float(2.))
+            ),
+            Err(OpError::ArgError)
+        ))
+    }
+}
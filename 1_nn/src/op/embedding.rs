@@ -1,4 +1,4 @@
-use super::{OpError, Operator, macros::*};
+use super::{OpError, Operator, gather::is_index, macros::*};
 use crate::{Arg, TensorMeta};
 use arg::make_eq;
 
@@ -33,3 +33,25 @@ impl Operator for Embedding {
         }
     }
 }
+
+/// 词表并行的查表，参数为本分片第一行的词表下标 `Int`。
+///
+/// 输入为 `wte: [row, d]` 和整数类型的 `tokens: [n]`，词表下标不在 `start..start + row` 中的 token 输出 0，
+/// 各分片的结果相加即为完整的查表结果
+pub struct MaskedEmbedding;
+
+impl Operator for MaskedEmbedding {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(Arg::Int(_start)) = args else {
+            return Err(OpError::ArgError);
+        };
+
+        destruct!([wte, tokens] = inputs);
+        if !is_index(tokens.dt) {
+            return Err(OpError::DataTypeError);
+        }
+        dims!([_, d] = wte);
+        dims!([n] = tokens);
+        Ok(vec![TensorMeta::new(wte.dt, [n.clone(), d.clone()])])
+    }
+}
@@ -0,0 +1,273 @@
+use super::{OpError, Operator, macros::*};
+use crate::{Arg, Dim, TensorMeta};
+use arg::make_eq;
+use tensor::digit_layout::{DigitLayout, types};
+
+/// 沿一个维度按下标取值。
+///
+/// 输入为 `x` 和与之同维数的 `index`，输出形状与 `index` 相同，
+/// `out[..., i, ...] = x[..., index[..., i, ...], ...]`，除 `axis` 以外各维度与 `x` 相等。
+pub struct Gather;
+
+impl Operator for Gather {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        destruct!([x, index] = inputs);
+        let axis = axis(args, x.shape().len())?;
+        if !is_index(index.dt) {
+            return Err(OpError::DataTypeError);
+        }
+
+        let shape = eq_except(index.shape(), x.shape(), axis)?;
+        Ok(vec![TensorMeta::new(x.dt, shape)])
+    }
+}
+
+/// 沿一个维度按一维下标选取切片。
+///
+/// 输入为 `x` 和 `index: [k]`，输出形状为 `x` 的 `axis` 维替换为 `k`。
+pub struct IndexSelect;
+
+impl Operator for IndexSelect {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        destruct!([x, index] = inputs);
+        let axis = axis(args, x.shape().len())?;
+        if !is_index(index.dt) {
+            return Err(OpError::DataTypeError);
+        }
+        dims!([k] = index);
+
+        let mut shape = x.shape().to_vec();
+        shape[axis] = k.clone();
+        Ok(vec![TensorMeta::new(x.dt, shape)])
+    }
+}
+
+/// [`Gather`] 的逆操作，沿一个维度按下标写入。
+///
+/// 输入为 `x`、`index` 和与 `index` 同形的 `src`，输出形状与 `x` 相同，
+/// `out[..., index[..., i, ...], ...] = src[..., i, ...]`，其余位置保持 `x` 的值。
+pub struct Scatter;
+
+impl Operator for Scatter {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        destruct!([x, index, src] = inputs);
+        let axis = axis(args, x.shape().len())?;
+        if !is_index(index.dt) {
+            return Err(OpError::DataTypeError);
+        }
+        if src.dt != x.dt {
+            return Err(OpError::DataTypeMismatch);
+        }
+
+        let shape = eq_except(index.shape(), src.shape(), usize::MAX)?;
+        eq_except(&shape, x.shape(), axis)?;
+        Ok(vec![x.clone()])
+    }
+}
+
+/// [`IndexSelect`] 的逆操作，沿一个维度按一维下标写入切片，可用于写入 kv cache。
+///
+/// 输入为 `x`、`index: [k]` 和 `values`，`values` 的形状为 `x` 的 `axis` 维替换为 `k`，
+/// 输出形状与 `x` 相同。
+pub struct IndexPut;
+
+impl Operator for IndexPut {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        destruct!([x, index, values] = inputs);
+        let axis = axis(args, x.shape().len())?;
+        if !is_index(index.dt) {
+            return Err(OpError::DataTypeError);
+        }
+        if values.dt != x.dt {
+            return Err(OpError::DataTypeMismatch);
+        }
+        dims!([k] = index);
+
+        let shape = eq_except(values.shape(), x.shape(), axis)?;
+        make_eq(&[&shape[axis], k]).ok_or(OpError::ShapeMismatch)?;
+        Ok(vec![x.clone()])
+    }
+}
+
+/// 下标必须是整数类型。
+pub(super) fn is_index(dt: DigitLayout) -> bool {
+    [
+        types::U8,
+        types::U16,
+        types::U32,
+        types::U64,
+        types::I8,
+        types::I16,
+        types::I32,
+        types::I64,
+    ]
+    .contains(&dt)
+}
+
+/// 解析参数 `{"axis": Int}`，`axis` 必须小于 `ndim`。
+fn axis(args: Option<&Arg>, ndim: usize) -> Result<usize, OpError> {
+    let Some(Arg::Dict(args)) = args else {
+        return Err(OpError::ArgError);
+    };
+    match args.get("axis") {
+        Some(&Arg::Int(axis)) if (axis as usize) < ndim => Ok(axis as _),
+        _ => Err(OpError::ArgError),
+    }
+}
+
+/// 要求两个形状维数相同且除 `axis` 以外的维度相等，返回带有相等约束的 `a`。
+fn eq_except(a: &[Dim], b: &[Dim], axis: usize) -> Result<Vec<Dim>, OpError> {
+    if a.len() != b.len() {
+        return Err(OpError::ShapeError);
+    }
+    a.iter()
+        .zip(b)
+        .enumerate()
+        .map(|(i, (a, b))| {
+            if i == axis {
+                Ok(a.clone())
+            } else {
+                make_eq(&[a, b]).ok_or(OpError::ShapeMismatch)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::op::test_utils::{meta, shape};
+
+    fn arg(axis: usize) -> Option<Arg> {
+        Some(Arg::dict([("axis".into(), Arg::int(axis))]))
+    }
+
+    #[test]
+    fn gather() {
+        let x = meta(types::F16, "n 8 d");
+        let y = Gather
+            .infer(&[x.clone(), meta(types::U32, "n 3 d")], arg(1).as_ref())
+            .unwrap();
+        assert_eq!(y[0].dt, types::F16);
+        assert_eq!(&*y[0].shape, &*shape("n 3 d"));
+        // 非 axis 维度不匹配
+        assert!(matches!(
+            Gather.infer(
+                &[meta(types::F16, "n 8 5"), meta(types::U32, "n 3 4")],
+                arg(1).as_ref()
+            ),
+            Err(OpError::ShapeMismatch)
+        ));
+        // 维数不同
+        assert!(matches!(
+            Gather.infer(&[x, meta(types::U32, "n 3")], arg(1).as_ref()),
+            Err(OpError::ShapeError)
+        ))
+    }
+
+    #[test]
+    fn index_select() {
+        let x = meta(types::F32, "n 8 d");
+        let y = IndexSelect
+            .infer(&[x.clone(), meta(types::I64, "k")], arg(0).as_ref())
+            .unwrap();
+        assert_eq!(&*y[0].shape, &*shape("k 8 d"));
+        // 下标必须是一维的
+        assert!(matches!(
+            IndexSelect.infer(&[x, meta(types::I64, "k 2")], arg(0).as_ref()),
+            Err(OpError::ShapeError)
+        ))
+    }
+
+    #[test]
+    fn index_dtype() {
+        let x = meta(types::F32, "n 8");
+        for dt in [
+            types::U8,
+            types::U16,
+            types::U32,
+            types::U64,
+            types::I8,
+            types::I16,
+            types::I32,
+            types::I64,
+        ] {
+            assert!(
+                IndexSelect
+                    .infer(&[x.clone(), meta(dt, "k")], arg(0).as_ref())
+                    .is_ok()
+            )
+        }
+        for dt in [types::F16, types::F32, types::BF16, types::Bool] {
+            assert!(matches!(
+                IndexSelect.infer(&[x.clone(), meta(dt, "k")], arg(0).as_ref()),
+                Err(OpError::DataTypeError)
+            ));
+            assert!(matches!(
+                Gather.infer(&[x.clone(), meta(dt, "n 8")], arg(0).as_ref()),
+                Err(OpError::DataTypeError)
+            ));
+            assert!(matches!(
+                Scatter.infer(
+                    &[x.clone(), meta(dt, "n 8"), meta(types::F32, "n 8")],
+                    arg(0).as_ref()
+                ),
+                Err(OpError::DataTypeError)
+            ));
+            assert!(matches!(
+                IndexPut.infer(
+                    &[x.clone(), meta(dt, "k"), meta(types::F32, "k 8")],
+                    arg(0).as_ref()
+                ),
+                Err(OpError::DataTypeError)
+            ))
+        }
+    }
+
+    #[test]
+    fn scatter_and_put() {
+        let x = meta(types::F32, "n 8");
+        let y = Scatter
+            .infer(
+                &[x.clone(), meta(types::I32, "n 2"), meta(types::F32, "n 2")],
+                arg(1).as_ref(),
+            )
+            .unwrap();
+        assert_eq!(&*y[0].shape, &*shape("n 8"));
+        assert!(matches!(
+            Scatter.infer(
+                &[x.clone(), meta(types::I32, "n 2"), meta(types::F16, "n 2")],
+                arg(1).as_ref()
+            ),
+            Err(OpError::DataTypeMismatch)
+        ));
+
+        let y = IndexPut
+            .infer(
+                &[x.clone(), meta(types::U32, "k"), meta(types::F32, "n k")],
+                arg(1).as_ref(),
+            )
+            .unwrap();
+        assert_eq!(&*y[0].shape, &*shape("n 8"));
+        // values 的 axis 维必须等于下标长度
+        assert!(matches!(
+            IndexPut.infer(
+                &[x.clone(), meta(types::U32, "3"), meta(types::F32, "n 2")],
+                arg(1).as_ref()
+            ),
+            Err(OpError::ShapeMismatch)
+        ));
+        // axis 越界或缺省
+        assert!(matches!(
+            IndexPut.infer(
+                &[x.clone(), meta(types::U32, "k"), meta(types::F32, "n k")],
+                arg(2).as_ref()
+            ),
+            Err(OpError::ArgError)
+        ));
+        assert!(matches!(
+            IndexPut.infer(&[x, meta(types::U32, "k"), meta(types::F32, "n k")], None),
+            Err(OpError::ArgError)
+        ))
+    }
+}
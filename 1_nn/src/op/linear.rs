@@ -1,6 +1,7 @@
 use super::{OpError, Operator, macros::*};
 use crate::{Arg, TensorMeta};
 use arg::make_eq;
+use tensor::digit_layout::types;
 
 pub struct Linear;
 
@@ -14,7 +15,7 @@ impl Operator for Linear {
                 dims!([m, k_x] = x);
                 dims!([n, k_w] = w);
 
-                if k_x != k_w {
+                if !k_x.expr_eq(k_w) {
                     return Err(OpError::ShapeMismatch);
                 }
 
@@ -25,7 +26,7 @@ impl Operator for Linear {
                 dims!([n, k_w] = w);
                 dims!([_n] = b);
 
-                if k_x != k_w {
+                if !k_x.expr_eq(k_w) {
                     return Err(OpError::ShapeMismatch);
                 }
                 let m = m.clone();
@@ -37,7 +38,7 @@ impl Operator for Linear {
                 dims!([_n, k_w] = w);
                 dims!([m, n] = residual);
 
-                if k_x != k_w {
+                if !k_x.expr_eq(k_w) {
                     return Err(OpError::ShapeMismatch);
                 }
 
@@ -51,7 +52,7 @@ impl Operator for Linear {
                 dims!([_n] = b);
                 dims!([m, n] = residual);
 
-                if k_x != k_w {
+                if !k_x.expr_eq(k_w) {
                     return Err(OpError::ShapeMismatch);
                 }
 
@@ -63,3 +64,201 @@ impl Operator for Linear {
         }
     }
 }
+
+/// 分组量化权重的线性层，参数为字典：
+///
+/// - `residual`: `Bool`，是否有残差输入；
+/// - `bits`: `Int`，每个权重的位数，为 2、4 或 8，每个 `U32` 打包 `pack = 32 / bits` 个权重；
+/// - `group`: `Int`，共享缩放和零点的权重数，必须是 `pack` 的倍数；
+/// - `zeros`: `Bool`，是否有零点输入，没有时为对称量化；
+/// - `bias`: `Bool`，是否有偏置输入；
+///
+/// 输入依次为 `x: [m, k]`、`residual: [m, n]`、`weight: U32[n, k / pack]`、`scales: [n, k / group]`、
+/// `zeros: U32[n, k / group / pack]` 和 `bias: [n]`，可选的输入不存在时跳过。
+pub struct QuantLinear;
+
+impl Operator for QuantLinear {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(Arg::Dict(args)) = args else {
+            return Err(OpError::ArgError);
+        };
+        let flag = |key: &str| match args.get(key) {
+            Some(&Arg::Bool(flag)) => Ok(flag),
+            _ => Err(OpError::ArgError),
+        };
+        let int = |key: &str| match args.get(key) {
+            Some(&Arg::Int(val)) if val > 0 => Ok(val as usize),
+            _ => Err(OpError::ArgError),
+        };
+        let residual = flag("residual")?;
+        let zeros = flag("zeros")?;
+        let bias = flag("bias")?;
+        let bits = int("bits")?;
+        let group = int("group")?;
+        if !matches!(bits, 2 | 4 | 8) {
+            return Err(OpError::ArgError);
+        }
+        let pack = 32 / bits;
+        if group % pack != 0 {
+            return Err(OpError::ArgError);
+        }
+
+        let n_inputs = 3 + residual as usize + zeros as usize + bias as usize;
+        if inputs.len() != n_inputs {
+            return Err(OpError::ShapeError);
+        }
+        let mut inputs = inputs.iter();
+        let mut next = || inputs.next().unwrap();
+
+        let x = next();
+        let residual = if residual { Some(next()) } else { None };
+        let (w, s) = (next(), next());
+        let z = if zeros { Some(next()) } else { None };
+        let b = if bias { Some(next()) } else { None };
+
+        dims!([m, k] = x);
+        dims!([n, k_w] = w);
+        dims!([n_s, g] = s);
+        if w.dt != types::U32 {
+            return Err(OpError::DataTypeMismatch);
+        }
+        // 打包的权重和每组的缩放覆盖同样的输入维度
+        make_eq(&[k, &(k_w.clone() * pack)]).ok_or(OpError::ShapeMismatch)?;
+        make_eq(&[k, &(g.clone() * group)]).ok_or(OpError::ShapeMismatch)?;
+        let mut n = make_eq(&[n, n_s]).ok_or(OpError::ShapeMismatch)?;
+        if let Some(z) = z {
+            dims!([n_z, g_z] = z);
+            if z.dt != types::U32 {
+                return Err(OpError::DataTypeMismatch);
+            }
+            make_eq(&[g, &(g_z.clone() * pack)]).ok_or(OpError::ShapeMismatch)?;
+            n = make_eq(&[&n, n_z]).ok_or(OpError::ShapeMismatch)?;
+        }
+        if let Some(b) = b {
+            dims!([n_b] = b);
+            n = make_eq(&[&n, n_b]).ok_or(OpError::ShapeMismatch)?;
+        }
+        let m = match residual {
+            Some(residual) => {
+                dims!([m_r, n_r] = residual);
+                n = make_eq(&[&n, n_r]).ok_or(OpError::ShapeMismatch)?;
+                make_eq(&[m, m_r]).ok_or(OpError::ShapeMismatch)?
+            }
+            None => m.clone(),
+        };
+
+        Ok(vec![TensorMeta::new(x.dt, [m, n])])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::op::test_utils::{meta, shape};
+
+    fn args(bits: usize, group: usize, residual: bool, zeros: bool, bias: bool) -> Arg {
+        Arg::dict([
+            ("residual".into(), Arg::bool(residual)),
+            ("bits".into(), Arg::int(bits)),
+            ("group".into(), Arg::int(group)),
+            ("zeros".into(), Arg::bool(zeros)),
+            ("bias".into(), Arg::bool(bias)),
+        ])
+    }
+
+    /// 4 位量化，每组 128 个权重，`k = 4096`
+    fn inputs(residual: bool, zeros: bool, bias: bool) -> Vec<TensorMeta> {
+        let mut inputs = vec![meta(types::F16, "m 4096")];
+        if residual {
+            inputs.push(meta(types::F16, "m n"))
+        }
+        inputs.push(meta(types::U32, "n 512"));
+        inputs.push(meta(types::F16, "n 32"));
+        if zeros {
+            inputs.push(meta(types::U32, "n 4"))
+        }
+        if bias {
+            inputs.push(meta(types::F16, "n"))
+        }
+        inputs
+    }
+
+    #[test]
+    fn packing() {
+        for residual in [false, true] {
+            for zeros in [false, true] {
+                for bias in [false, true] {
+                    let y = QuantLinear
+                        .infer(
+                            &inputs(residual, zeros, bias),
+                            Some(&args(4, 128, residual, zeros, bias)),
+                        )
+                        .unwrap();
+                    assert_eq!(y[0].dt, types::F16);
+                    assert_eq!(&*y[0].shape, &*shape("m n"))
+                }
+            }
+        }
+        // 8 位量化的权重每个 U32 打包 4 个
+        let inputs = [
+            meta(types::F16, "m 4096"),
+            meta(types::U32, "n 1024"),
+            meta(types::F16, "n 64"),
+        ];
+        assert!(
+            QuantLinear
+                .infer(&inputs, Some(&args(8, 64, false, false, false)))
+                .is_ok()
+        )
+    }
+
+    #[test]
+    fn invalid_args() {
+        let inputs = inputs(false, true, false);
+        // 位数只能是 2、4、8
+        assert!(matches!(
+            QuantLinear.infer(&inputs, Some(&args(3, 128, false, true, false))),
+            Err(OpError::ArgError)
+        ));
+        // 每组的权重数必须是打包数的倍数
+        assert!(matches!(
+            QuantLinear.infer(&inputs, Some(&args(4, 12, false, true, false))),
+            Err(OpError::ArgError)
+        ));
+        assert!(matches!(
+            QuantLinear.infer(&inputs, Some(&args(4, 0, false, true, false))),
+            Err(OpError::ArgError)
+        ));
+        // 输入数量与参数不一致
+        assert!(matches!(
+            QuantLinear.infer(&inputs, Some(&args(4, 128, false, false, false))),
+            Err(OpError::ShapeError)
+        ))
+    }
+
+    #[test]
+    fn mismatch() {
+        let infer = |inputs: &[TensorMeta]| {
+            QuantLinear.infer(inputs, Some(&args(4, 128, false, true, false)))
+        };
+        let mut inputs = inputs(false, true, false);
+        assert!(infer(&inputs).is_ok());
+        // 打包的权重与输入维度不符
+        inputs[1] = meta(types::U32, "n 256");
+        assert!(matches!(infer(&inputs), Err(OpError::ShapeMismatch)));
+        // 每组的缩放与输入维度不符
+        inputs[1] = meta(types::U32, "n 512");
+        inputs[2] = meta(types::F16, "n 64");
+        assert!(matches!(infer(&inputs), Err(OpError::ShapeMismatch)));
+        // 零点也按打包存储
+        inputs[2] = meta(types::F16, "n 32");
+        inputs[3] = meta(types::U32, "n 32");
+        assert!(matches!(infer(&inputs), Err(OpError::ShapeMismatch)));
+        // 权重和零点必须是 U32
+        inputs[3] = meta(types::F16, "n 4");
+        assert!(matches!(infer(&inputs), Err(OpError::DataTypeMismatch)));
+        inputs[3] = meta(types::U32, "n 4");
+        inputs[1] = meta(types::F16, "n 512");
+        assert!(matches!(infer(&inputs), Err(OpError::DataTypeMismatch)))
+    }
+}
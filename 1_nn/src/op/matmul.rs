@@ -0,0 +1,76 @@
+use super::{OpError, Operator, element_wise::broadcast_shape, macros::*};
+use crate::{Arg, TensorMeta};
+use arg::make_eq;
+
+/// 批量矩阵乘 `[..., m, k] x [..., k, n] -> [..., m, n]`，批维度按 NumPy 规则广播
+pub struct MatMul;
+
+impl Operator for MatMul {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        if args.is_some() {
+            return Err(OpError::ArgError);
+        }
+
+        destruct!([a, b] = inputs);
+        let [batch_a @ .., m, k_a] = a.shape() else {
+            return Err(OpError::ShapeError);
+        };
+        let [batch_b @ .., k_b, n] = b.shape() else {
+            return Err(OpError::ShapeError);
+        };
+
+        make_eq(&[k_a, k_b]).ok_or(OpError::ShapeMismatch)?;
+        let mut shape = broadcast_shape(batch_a, batch_b).ok_or(OpError::ShapeMismatch)?;
+        shape.extend([m.clone(), n.clone()]);
+        Ok(vec![TensorMeta::new(a.dt, shape)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::op::test_utils::{meta, shape};
+    use tensor::digit_layout::types;
+
+    fn infer(a: &str, b: &str) -> Result<TensorMeta, OpError> {
+        let inputs = [meta(types::F32, a), meta(types::F32, b)];
+        Ok(MatMul.infer(&inputs, None)?.pop().unwrap())
+    }
+
+    #[test]
+    fn broadcast() {
+        for (a, b, expected) in [
+            ("m k", "k n", "m n"),
+            ("b m k", "k n", "b m n"),
+            ("m k", "b k n", "b m n"),
+            ("2 1 m 64", "3 64 n", "2 3 m n"),
+            ("1 h m k", "b 1 k n", "b h m n"),
+        ] {
+            let y = infer(a, b).unwrap();
+            assert_eq!(&*y.shape, &*shape(expected), "{a} x {b}")
+        }
+    }
+
+    #[test]
+    fn mismatch() {
+        // 收缩维度不等
+        assert!(matches!(
+            infer("4 3 64", "4 32 8"),
+            Err(OpError::ShapeMismatch)
+        ));
+        // 批维度不能广播
+        assert!(matches!(
+            infer("2 4 8", "3 8 4"),
+            Err(OpError::ShapeMismatch)
+        ));
+        // 至少二维
+        assert!(matches!(infer("k", "k n"), Err(OpError::ShapeError)));
+        assert!(matches!(
+            MatMul.infer(
+                &[meta(types::F32, "m k"), meta(types::F32, "k n")],
+                Some(&Arg::int(0))
+            ),
+            Err(OpError::ArgError)
+        ))
+    }
+}
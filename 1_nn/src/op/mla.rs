@@ -0,0 +1,64 @@
+use super::{OpError, Operator};
+use crate::{Arg, Dim, TensorMeta};
+use arg::make_eq;
+
+/// 多头潜在注意力，分数为 `q_nope·k_nope + q_pe·k_pe`。
+///
+/// 输入依次为：
+///
+/// - `q_nope: [n, nh, dk]`、`q_pe: [n, nh, dr]`；
+/// - `k_nope: [n, nh, dk]` 或所有头共享的 `[n, dk]`；
+/// - 所有头共享的 `k_pe: [n, dr]`；
+/// - `v: [n, nh, dv]` 或所有头共享的 `[n, dv]`；
+///
+/// 输出为 `[n, nh, dv]`。吸收权重的推理形式中 `k_nope` 和 `v` 都是压缩后的 kv。
+/// 参数为字典，可以省略，`scale` 为 `Float`，默认为 `1 / sqrt(dk + dr)`。
+pub struct Mla;
+
+impl Operator for Mla {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        match args {
+            Some(Arg::Dict(args)) => match args.get("scale") {
+                Some(Arg::Float(_)) | None => {}
+                Some(_) => return Err(OpError::ArgError),
+            },
+            None => {}
+            Some(_) => return Err(OpError::ArgError),
+        }
+
+        let [q_nope, q_pe, k_nope, k_pe, v] = inputs else {
+            return Err(OpError::ShapeError);
+        };
+        let [n, nh, dk] = q_nope.shape() else {
+            return Err(OpError::ShapeError);
+        };
+        let [n_q_pe, nh_q_pe, dr] = q_pe.shape() else {
+            return Err(OpError::ShapeError);
+        };
+        let [n_k_pe, dr_k] = k_pe.shape() else {
+            return Err(OpError::ShapeError);
+        };
+        let nh = make_eq(&[nh, nh_q_pe]).ok_or(OpError::ShapeMismatch)?;
+        make_eq(&[dr, dr_k]).ok_or(OpError::ShapeMismatch)?;
+
+        // k 和 v 可以每个头独立，也可以所有头共享
+        let (n_k, dk_) = per_head(k_nope, &nh)?;
+        let (n_v, dv) = per_head(v, &nh)?;
+        make_eq(&[dk, dk_]).ok_or(OpError::ShapeMismatch)?;
+
+        let n = make_eq(&[n, n_q_pe, n_k_pe, n_k, n_v]).ok_or(OpError::ShapeMismatch)?;
+        Ok(vec![TensorMeta::new(q_nope.dt, [n, nh, dv.clone()])])
+    }
+}
+
+/// 返回 `[n, nh, d]` 或 `[n, d]` 的 `n` 和 `d`
+fn per_head<'a>(t: &'a TensorMeta, nh: &Dim) -> Result<(&'a Dim, &'a Dim), OpError> {
+    match t.shape() {
+        [n, nh_, d] => {
+            make_eq(&[nh, nh_]).ok_or(OpError::ShapeMismatch)?;
+            Ok((n, d))
+        }
+        [n, d] => Ok((n, d)),
+        _ => Err(OpError::ShapeError),
+    }
+}
@@ -1,17 +1,31 @@
 ﻿use crate::{Arg, TensorMeta};
 
 pub mod activation;
-pub mod add;
+/// 兼容旧的路径，`Add` 已合并到 [`element_wise`]
+pub mod add {
+    pub use super::element_wise::Add;
+}
+pub mod all_gather;
 pub mod all_reduce;
 pub mod attention;
+pub mod broadcast;
 pub mod concat;
 pub mod conv;
+pub mod element_wise;
 pub mod embedding;
+pub mod gather;
 pub mod linear;
+pub mod matmul;
 pub mod merge;
+pub mod mla;
 pub mod mrope;
 pub mod normalization;
+pub mod p2p;
+pub mod pad;
+pub mod reduce;
+pub mod reduce_scatter;
 pub mod rope;
+pub mod slice;
 pub mod split;
 pub mod tile;
 pub mod transpose;
@@ -52,3 +66,20 @@ pub mod macros {
 
     pub(crate) use {destruct, dims};
 }
+
+#[cfg(test)]
+mod test_utils {
+    use crate::{Dim, TensorMeta};
+    use tensor::digit_layout::DigitLayout;
+
+    /// 按空格分隔的形状，数字为常数，其他为变量名
+    pub(super) fn shape(s: &str) -> Vec<Dim> {
+        s.split_whitespace()
+            .map(|d| d.parse::<usize>().map_or_else(|_| Dim::from(d), Dim::from))
+            .collect()
+    }
+
+    pub(super) fn meta(dt: DigitLayout, s: &str) -> TensorMeta {
+        TensorMeta::new(dt, shape(s))
+    }
+}
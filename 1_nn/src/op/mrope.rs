@@ -13,33 +13,40 @@ impl Operator for Mrope {
                 dims!([n_ctx_sin, dh_2_sin] = sin);
                 dims!([n_ctx_cos, dh_2_cos] = cos);
 
-                match d_pos.to_usize() {
+                // 2D 的 sin/cos 宽度为 dim / 4，3D 为 dim / 2
+                let dim = match d_pos.to_usize() {
                     2 => {
                         if args.is_some() {
                             return Err(OpError::ArgError);
                         }
+                        dh_2_sin.clone() * 4
                     }
                     3 => {
                         let Some(Arg::Arr(_mrope_section)) = args else {
                             return Err(OpError::ArgError);
                         };
+                        dh_2_sin.clone() * 2
                     }
                     _ => return Err(OpError::ShapeError),
-                }
+                };
 
                 // Check if context lengths match
-                if n_ctx_sin != n_ctx_cos {
+                if !n_ctx_sin.expr_eq(n_ctx_cos) {
                     return Err(OpError::ShapeMismatch);
                 }
 
                 // Check if half embedding dimensions match
-                if dh_2_sin != dh_2_cos {
+                if !dh_2_sin.expr_eq(dh_2_cos) {
                     return Err(OpError::ShapeMismatch);
                 }
 
                 let _n = make_eq(&[&x.shape[0], n_pos]).ok_or(OpError::ShapeMismatch)?;
 
-                Ok(vec![TensorMeta::new(x.dt, [_n, _d.clone()])])
+                // 每个头的维度都参与旋转
+                let _d = make_eq(&[_d, &(_d.clone() / dim.clone() * dim)])
+                    .ok_or(OpError::ShapeMismatch)?;
+
+                Ok(vec![TensorMeta::new(x.dt, [_n, _d])])
             }
             _ => Err(OpError::ShapeError),
         }
@@ -2,13 +2,24 @@ use super::{OpError, Operator, macros::*};
 use crate::{Arg, TensorMeta};
 use arg::make_eq;
 
+/// 参数为 `Float` epsilon，或 `{epsilon: Float, offset: Float}`，
+/// 带 `offset` 时缩放系数为 `offset + scale`（Gemma 中为 1）
 pub struct RmsNorm;
 
 impl Operator for RmsNorm {
     fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
-        let _epsilon = args.ok_or(OpError::ArgError)?;
-
-        // epsilon是浮点数
+        match args {
+            Some(Arg::Float(_)) => {}
+            Some(Arg::Dict(args)) => {
+                let Some(Arg::Float(_)) = args.get("epsilon") else {
+                    return Err(OpError::ArgError);
+                };
+                if !matches!(args.get("offset"), None | Some(Arg::Float(_))) {
+                    return Err(OpError::ArgError);
+                }
+            }
+            _ => return Err(OpError::ArgError),
+        }
 
         match inputs {
             [x, scale] => {
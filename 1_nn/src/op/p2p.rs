@@ -0,0 +1,35 @@
+use super::{OpError, Operator, macros::*};
+use crate::{Arg, TensorMeta};
+
+/// 把输入发送给另一个流水线阶段，参数为对方的阶段序号 `Int`。
+///
+/// 没有输出，执行时需要保持到发送完成
+pub struct Send;
+
+/// 从另一个流水线阶段接收数据写入输入，参数为对方的阶段序号 `Int`。
+///
+/// 输入是隐状态的占位，只提供形状和存储，输出与输入共享存储
+pub struct Recv;
+
+impl Operator for Send {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        check_p2p(inputs, args)?;
+        Ok(vec![])
+    }
+}
+
+impl Operator for Recv {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let x = check_p2p(inputs, args)?;
+        Ok(vec![x.clone()])
+    }
+}
+
+fn check_p2p<'a>(inputs: &'a [TensorMeta], args: Option<&Arg>) -> Result<&'a TensorMeta, OpError> {
+    let Some(Arg::Int(_peer)) = args else {
+        return Err(OpError::ArgError);
+    };
+
+    destruct!([x] = inputs);
+    Ok(x)
+}
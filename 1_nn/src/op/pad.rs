@@ -0,0 +1,120 @@
+use super::{OpError, Operator, macros::*};
+use crate::{Arg, Dim, TensorMeta};
+
+/// 在每个维度两侧填充常数。
+///
+/// 参数为 `{"before": [Dim], "after": [Dim], "value": Float}`，
+/// `before` 和 `after` 的长度等于输入维数，`value` 可省略，默认为 0。
+/// 每个维度的输出为 `before + x + after`。
+pub struct Pad;
+
+impl Operator for Pad {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(Arg::Dict(args)) = args else {
+            return Err(OpError::ArgError);
+        };
+        let before = dims_arg(args.get("before"))?;
+        let after = dims_arg(args.get("after"))?;
+        match args.get("value") {
+            Some(Arg::Float(_)) | None => {}
+            Some(_) => return Err(OpError::ArgError),
+        }
+
+        destruct!([x] = inputs);
+
+        let shape = x.shape();
+        if before.len() != shape.len() || after.len() != shape.len() {
+            return Err(OpError::ShapeError);
+        }
+        if x.dt.group_size() > 1 {
+            return Err(OpError::DataTypeError);
+        }
+
+        let shape = shape
+            .iter()
+            .zip(before)
+            .zip(after)
+            .map(|((d, b), a)| b + d.clone() + a)
+            .collect::<Vec<_>>();
+        Ok(vec![TensorMeta::new(x.dt, shape)])
+    }
+}
+
+fn dims_arg(arg: Option<&Arg>) -> Result<Vec<Dim>, OpError> {
+    let Some(Arg::Arr(arr)) = arg else {
+        return Err(OpError::ArgError);
+    };
+    arr.iter()
+        .map(|a| match a {
+            Arg::Dim(dim) => Ok(dim.clone()),
+            &Arg::Int(val) => Ok(Dim::from(val as usize)),
+            _ => Err(OpError::ArgError),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::op::test_utils::{meta, shape};
+    use tensor::digit_layout::{DigitLayout, types};
+
+    fn arg(before: Vec<Dim>, after: Vec<Dim>) -> Arg {
+        Arg::dict([
+            ("before".into(), Arg::arr(before.into_iter().map(Arg::dim))),
+            ("after".into(), Arg::arr(after.into_iter().map(Arg::dim))),
+            ("value".into(), Arg::float(-1.)),
+        ])
+    }
+
+    #[test]
+    fn pad() {
+        let y = Pad
+            .infer(
+                &[meta(types::F32, "2 3")],
+                Some(&arg(shape("1 0"), shape("2 4"))),
+            )
+            .unwrap();
+        assert_eq!(&*y[0].shape, &*shape("5 7"));
+        // 符号填充
+        let y = Pad
+            .infer(
+                &[meta(types::F32, "n 3")],
+                Some(&arg(shape("p 0"), shape("0 0"))),
+            )
+            .unwrap();
+        assert_eq!(y[0].shape[0], Dim::from("p") + Dim::from("n"));
+        assert_eq!(y[0].shape[1], Dim::from(3))
+    }
+
+    #[test]
+    fn invalid() {
+        // 填充长度与维数不一致
+        assert!(matches!(
+            Pad.infer(
+                &[meta(types::F32, "2 3")],
+                Some(&arg(shape("1"), shape("1 1")))
+            ),
+            Err(OpError::ShapeError)
+        ));
+        // 量化类型不能填充
+        let q8_0 = DigitLayout::named("Q8_0", 32, 34);
+        assert!(matches!(
+            Pad.infer(
+                &[meta(q8_0, "2 32")],
+                Some(&arg(shape("0 0"), shape("1 0")))
+            ),
+            Err(OpError::DataTypeError)
+        ));
+        // value 必须是浮点数
+        let arg = Arg::dict([
+            ("before".into(), Arg::arr([Arg::int(0)])),
+            ("after".into(), Arg::arr([Arg::int(1)])),
+            ("value".into(), Arg::int(0)),
+        ]);
+        assert!(matches!(
+            Pad.infer(&[meta(types::F32, "2")], Some(&arg)),
+            Err(OpError::ArgError)
+        ))
+    }
+}
@@ -0,0 +1,191 @@
+use super::{OpError, Operator, macros::*};
+use crate::{Arg, Dim, TensorMeta};
+use tensor::digit_layout::types;
+
+macro_rules! reduce {
+    ($( $(#[$doc:meta])* $name:ident )+) => {
+        $(
+            $(#[$doc])*
+            pub struct $name;
+
+            impl Operator for $name {
+                fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+                    destruct!([x] = inputs);
+                    let (axes, keep_dims) = reduce_args(args, x.shape().len())?;
+                    if x.dt.group_size() > 1 {
+                        return Err(OpError::DataTypeError);
+                    }
+                    Ok(vec![TensorMeta::new(x.dt, reduce_shape(x.shape(), &axes, keep_dims))])
+                }
+            }
+        )+
+    };
+}
+
+reduce! {
+    /// 沿指定维度求和
+    ReduceSum
+    /// 沿指定维度求均值
+    ReduceMean
+    /// 沿指定维度求最大值
+    ReduceMax
+    /// 沿指定维度求最小值
+    ReduceMin
+}
+
+/// 沿一个维度求最大值的下标，输出类型为 `u32`。
+///
+/// 参数同其他归约算子，但 `axes` 必须恰好包含一个维度。
+pub struct ArgMax;
+
+impl Operator for ArgMax {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        destruct!([x] = inputs);
+        let (axes, keep_dims) = reduce_args(args, x.shape().len())?;
+        if axes.iter().filter(|&&reduce| reduce).count() != 1 {
+            return Err(OpError::ArgError);
+        }
+        if x.dt.group_size() > 1 {
+            return Err(OpError::DataTypeError);
+        }
+        Ok(vec![TensorMeta::new(
+            types::U32,
+            reduce_shape(x.shape(), &axes, keep_dims),
+        )])
+    }
+}
+
+/// 解析归约参数，参数为字典，各项均可省略：
+///
+/// - `axes`: `[Int]`，归约的维度，不能重复，省略或为空时归约所有维度；
+/// - `keep_dims`: `Bool`，是否保留归约的维度为 1，默认为 `false`；
+fn reduce_args(args: Option<&Arg>, ndim: usize) -> Result<(Vec<bool>, bool), OpError> {
+    let args = match args {
+        Some(Arg::Dict(args)) => Some(args),
+        None => None,
+        Some(_) => return Err(OpError::ArgError),
+    };
+
+    let axes = match args.and_then(|args| args.get("axes")) {
+        Some(Arg::Arr(arr)) => {
+            let mut mask = vec![false; ndim];
+            for axis in arr {
+                match axis {
+                    &Arg::Int(axis) if (axis as usize) < ndim && !mask[axis as usize] => {
+                        mask[axis as usize] = true
+                    }
+                    _ => return Err(OpError::ArgError),
+                }
+            }
+            if mask.contains(&true) {
+                mask
+            } else {
+                vec![true; ndim]
+            }
+        }
+        Some(_) => return Err(OpError::ArgError),
+        None => vec![true; ndim],
+    };
+    let keep_dims = match args.and_then(|args| args.get("keep_dims")) {
+        Some(&Arg::Bool(keep_dims)) => keep_dims,
+        Some(_) => return Err(OpError::ArgError),
+        None => false,
+    };
+    Ok((axes, keep_dims))
+}
+
+fn reduce_shape(shape: &[Dim], axes: &[bool], keep_dims: bool) -> Vec<Dim> {
+    shape
+        .iter()
+        .zip(axes)
+        .filter_map(|(d, &reduce)| match (reduce, keep_dims) {
+            (false, _) => Some(d.clone()),
+            (true, true) => Some(Dim::from(1)),
+            (true, false) => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::op::test_utils::{meta, shape};
+    use tensor::digit_layout::DigitLayout;
+
+    fn arg(axes: &[usize], keep_dims: bool) -> Arg {
+        Arg::dict([
+            ("axes".into(), Arg::arr(axes.iter().map(|&a| Arg::int(a)))),
+            ("keep_dims".into(), Arg::bool(keep_dims)),
+        ])
+    }
+
+    fn infer(op: &dyn Operator, x: &str, arg: Option<Arg>) -> Result<TensorMeta, OpError> {
+        let mut outputs = op.infer(&[meta(types::F32, x)], arg.as_ref())?;
+        assert_eq!(outputs.len(), 1);
+        Ok(outputs.pop().unwrap())
+    }
+
+    #[test]
+    fn keep_dims() {
+        let cases = [
+            (&[1][..], false, "n 4"),
+            (&[1], true, "n 1 4"),
+            (&[2, 0], false, "3"),
+            (&[2, 0], true, "1 3 1"),
+            // 空的 axes 归约所有维度
+            (&[], false, ""),
+            (&[], true, "1 1 1"),
+        ];
+        for (axes, keep_dims, expected) in cases {
+            for op in [
+                &ReduceSum as &dyn Operator,
+                &ReduceMean,
+                &ReduceMax,
+                &ReduceMin,
+            ] {
+                let y = infer(op, "n 3 4", Some(arg(axes, keep_dims))).unwrap();
+                assert_eq!(y.dt, types::F32);
+                assert_eq!(&*y.shape, &*shape(expected), "{axes:?} {keep_dims}")
+            }
+        }
+        // 省略参数时归约所有维度，不保留
+        let y = infer(&ReduceSum, "n 3 4", None).unwrap();
+        assert!(y.shape.is_empty())
+    }
+
+    #[test]
+    fn argmax() {
+        let y = infer(&ArgMax, "n 32", Some(arg(&[1], false))).unwrap();
+        assert_eq!(y.dt, types::U32);
+        assert_eq!(&*y.shape, &*shape("n"));
+        let y = infer(&ArgMax, "n 32", Some(arg(&[1], true))).unwrap();
+        assert_eq!(&*y.shape, &*shape("n 1"));
+        // 必须恰好归约一个维度
+        assert!(matches!(
+            infer(&ArgMax, "n 32", Some(arg(&[0, 1], false))),
+            Err(OpError::ArgError)
+        ));
+        assert!(matches!(
+            infer(&ArgMax, "n 32", None),
+            Err(OpError::ArgError)
+        ))
+    }
+
+    #[test]
+    fn invalid() {
+        // 维度越界或重复
+        assert!(matches!(
+            infer(&ReduceSum, "2 3", Some(arg(&[2], false))),
+            Err(OpError::ArgError)
+        ));
+        assert!(matches!(
+            infer(&ReduceSum, "2 3", Some(arg(&[1, 1], false))),
+            Err(OpError::ArgError)
+        ));
+        // 量化类型不能归约
+        assert!(matches!(
+            ReduceSum.infer(&[meta(DigitLayout::named("Q8_0", 32, 34), "2 32")], None),
+            Err(OpError::DataTypeError)
+        ))
+    }
+}
@@ -0,0 +1,37 @@
+use super::{OpError, Operator, all_gather::ranks_axis, all_reduce::is_reduce_op, macros::*};
+use crate::{Arg, TensorMeta};
+use std::collections::HashMap;
+
+/// 规约 `ranks` 个 rank 的输入，结果沿一个维度均分，每个 rank 得到自己的一份。
+///
+/// 参数为 `{"op": Str, "ranks": Int, "axis": Int}`，`op` 与 `all-reduce` 相同，`axis` 可省略，默认为 0。
+/// 切分的维度为常数时必须能被 `ranks` 整除。
+pub struct ReduceScatter;
+
+impl Operator for ReduceScatter {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(Arg::Dict(args)) = args else {
+            return Err(OpError::ArgError);
+        };
+        let Some(&Arg::Str(op)) = args.get("op") else {
+            return Err(OpError::ArgError);
+        };
+        if !is_reduce_op(op) {
+            return Err(OpError::ArgError);
+        }
+        let (ranks, axis) = ranks_axis(args)?;
+
+        destruct!([x] = inputs);
+        let mut shape = x.shape().to_vec();
+        let Some(d) = shape.get_mut(axis) else {
+            return Err(OpError::ShapeError);
+        };
+        if d.substitute(&HashMap::new())
+            .is_some_and(|d| !d.is_multiple_of(ranks))
+        {
+            return Err(OpError::ShapeMismatch);
+        }
+        *d = d.clone() / ranks;
+        Ok(vec![TensorMeta::new(x.dt, shape)])
+    }
+}
@@ -1,7 +1,13 @@
 use super::{OpError, Operator, macros::*};
 use crate::{Arg, TensorMeta};
 use arg::make_eq;
+use std::collections::{BTreeSet, HashMap};
 
+/// 旋转位置编码。
+///
+/// 输入为 `x`、`pos: [n]` 和 `sin`/`cos: [nctx, dim / 2]`，`x` 为 `[n, d]` 时每个头的维度都参与旋转，
+/// 为 `[n, nh, dh]` 时只旋转每个头的前 `dim` 维，其余维度保持不变。
+/// 前者要求 `d` 能被 `dim` 整除，后者要求 `dim` 不大于 `dh`。
 pub struct Rope;
 
 impl Operator for Rope {
@@ -12,24 +18,47 @@ impl Operator for Rope {
 
         match inputs {
             [x, pos, sin, cos] => {
-                dims!([_n, _d] = x);
+                let [_n, rest @ ..] = x.shape() else {
+                    return Err(OpError::ShapeError);
+                };
+                if !(1..=2).contains(&rest.len()) {
+                    return Err(OpError::ShapeError);
+                }
                 dims!([n_pos] = pos);
                 dims!([n_ctx_sin, dh_2_sin] = sin);
                 dims!([n_ctx_cos, dh_2_cos] = cos);
 
                 // Check if context lengths match
-                if n_ctx_sin != n_ctx_cos {
+                if !n_ctx_sin.expr_eq(n_ctx_cos) {
                     return Err(OpError::ShapeMismatch);
                 }
 
                 // Check if half embedding dimensions match
-                if dh_2_sin != dh_2_cos {
+                if !dh_2_sin.expr_eq(dh_2_cos) {
                     return Err(OpError::ShapeMismatch);
                 }
 
-                let _n = make_eq(&[&x.shape[0], n_pos]).ok_or(OpError::ShapeMismatch)?;
+                let _n = make_eq(&[_n, n_pos]).ok_or(OpError::ShapeMismatch)?;
 
-                Ok(vec![TensorMeta::new(x.dt, [_n, _d.clone()])])
+                let dim = dh_2_sin.clone() * 2;
+                let mut shape = vec![_n];
+                match rest {
+                    [d] => shape.push(
+                        make_eq(&[d, &(d.clone() / dim.clone() * dim)])
+                            .ok_or(OpError::ShapeMismatch)?,
+                    ),
+                    [nh, dh] => {
+                        let rest = dh.clone() - dim;
+                        let mut vars = BTreeSet::new();
+                        rest.append_variables(&mut vars);
+                        if vars.is_empty() && rest.substitute(&HashMap::new()).is_none() {
+                            return Err(OpError::ShapeMismatch);
+                        }
+                        shape.extend([nh.clone(), dh.clone()])
+                    }
+                    _ => unreachable!(),
+                }
+                Ok(vec![TensorMeta::new(x.dt, shape)])
             }
             _ => Err(OpError::ShapeError),
         }
@@ -0,0 +1,125 @@
+use super::{OpError, Operator, macros::*};
+use crate::{Arg, Dim, TensorMeta};
+use std::collections::HashMap;
+
+/// 沿一个维度按步长取出一段，即 `x[.., start..start + len * step; step, ..]`。
+///
+/// 参数为 `{"axis": Int, "start": Dim, "len": Dim, "step": Int}`，`step` 可省略，默认为 1。
+/// 起点和长度可以是符号，只有全部为常数时才检查是否越界。
+pub struct Slice;
+
+impl Operator for Slice {
+    fn infer(&self, inputs: &[TensorMeta], args: Option<&Arg>) -> Result<Vec<TensorMeta>, OpError> {
+        let Some(Arg::Dict(args)) = args else {
+            return Err(OpError::ArgError);
+        };
+        let Some(&Arg::Int(axis)) = args.get("axis") else {
+            return Err(OpError::ArgError);
+        };
+        let start = dim_arg(args.get("start"))?;
+        let len = dim_arg(args.get("len"))?;
+        let step = match args.get("step") {
+            Some(&Arg::Int(step)) if step > 0 => step as usize,
+            Some(_) => return Err(OpError::ArgError),
+            None => 1,
+        };
+
+        destruct!([x] = inputs);
+
+        let axis = axis as usize;
+        let mut shape = x.shape().to_vec();
+        if axis >= shape.len() {
+            return Err(OpError::ShapeError);
+        }
+
+        let constant = |d: &Dim| d.substitute(&HashMap::new());
+        if let (Some(d), Some(start), Some(len)) =
+            (constant(&shape[axis]), constant(&start), constant(&len))
+            && len > 0
+            && start + (len - 1) * step >= d
+        {
+            return Err(OpError::ShapeMismatch);
+        }
+
+        shape[axis] = len;
+        Ok(vec![TensorMeta::new(x.dt, shape)])
+    }
+}
+
+fn dim_arg(arg: Option<&Arg>) -> Result<Dim, OpError> {
+    match arg {
+        Some(Arg::Dim(dim)) => Ok(dim.clone()),
+        Some(&Arg::Int(val)) => Ok(Dim::from(val as usize)),
+        _ => Err(OpError::ArgError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::op::test_utils::{meta, shape};
+    use tensor::digit_layout::types;
+
+    fn arg(axis: usize, start: impl Into<Dim>, len: impl Into<Dim>, step: usize) -> Arg {
+        Arg::dict([
+            ("axis".into(), Arg::int(axis)),
+            ("start".into(), Arg::dim(start)),
+            ("len".into(), Arg::dim(len)),
+            ("step".into(), Arg::int(step)),
+        ])
+    }
+
+    fn infer(x: &str, arg: Arg) -> Result<TensorMeta, OpError> {
+        Ok(Slice
+            .infer(&[meta(types::F32, x)], Some(&arg))?
+            .pop()
+            .unwrap())
+    }
+
+    #[test]
+    fn bounds() {
+        // 最后一个元素恰好在范围内
+        let y = infer("n 10", arg(1, 1, 3, 4)).unwrap();
+        assert_eq!(&*y.shape, &*shape("n 3"));
+        let y = infer("n 10", arg(1, 0, 10, 1)).unwrap();
+        assert_eq!(&*y.shape, &*shape("n 10"));
+        // 越界
+        assert!(matches!(
+            infer("n 10", arg(1, 2, 3, 4)),
+            Err(OpError::ShapeMismatch)
+        ));
+        assert!(matches!(
+            infer("n 10", arg(1, 10, 1, 1)),
+            Err(OpError::ShapeMismatch)
+        ));
+        // 空切片不检查起点
+        let y = infer("n 10", arg(1, 10, 0, 1)).unwrap();
+        assert_eq!(&*y.shape, &*shape("n 0"));
+        // 含符号时不检查
+        let y = infer("n 10", arg(0, 100, 2, 1)).unwrap();
+        assert_eq!(&*y.shape, &*shape("2 10"));
+        let y = infer("n 10", arg(1, "p", "l", 1)).unwrap();
+        assert_eq!(&*y.shape, &*shape("n l"))
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            infer("n 10", arg(2, 0, 1, 1)),
+            Err(OpError::ShapeError)
+        ));
+        assert!(matches!(
+            infer("n 10", arg(1, 0, 1, 0)),
+            Err(OpError::ArgError)
+        ));
+        // 省略 step 默认为 1
+        let arg = Arg::dict([
+            ("axis".into(), Arg::int(1)),
+            ("start".into(), Arg::int(9)),
+            ("len".into(), Arg::int(1)),
+        ]);
+        assert_eq!(&*infer("n 10", arg).unwrap().shape, &*shape("n 1"));
+        let arg = Arg::dict([("axis".into(), Arg::int(1)), ("start".into(), Arg::int(0))]);
+        assert!(matches!(infer("n 10", arg), Err(OpError::ArgError)))
+    }
+}
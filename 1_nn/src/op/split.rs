@@ -38,7 +38,7 @@ impl Operator for Split {
         let sum = parts.iter().fold(Dim::from(0), |acc, p| acc + p.clone());
 
         let c = shape[axis].clone() / sum.clone();
-        if c.clone() * sum != shape[axis] {
+        if !(c.clone() * sum).expr_eq(&shape[axis]) {
             return Err(OpError::ShapeMismatch);
         }
 
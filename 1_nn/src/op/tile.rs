@@ -37,7 +37,7 @@ impl Operator for Tile {
 
         let tile_product = tile.iter().fold(Dim::from(1), |acc, t| acc * t.clone());
 
-        if tile_product != shape[axis] {
+        if !tile_product.expr_eq(&shape[axis]) {
             return Err(OpError::ShapeError);
         }
 
@@ -0,0 +1,51 @@
+use crate::{Dim, Edge, External, NNGraph, TPAction, TPTensor, Tensor};
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    hash::Hash,
+};
+
+impl<T: Clone + Eq + Hash> NNGraph<TPTensor<T>> {
+    /// 按每个外部张量的切分方式，从 `load` 给出的完整张量中取出本分片的数据。
+    ///
+    /// 同一个张量只取一次，结果的形状与计算图中一致，加载时不需要再切分。
+    pub fn shard_weights<'a>(
+        &self,
+        mut load: impl FnMut(&T) -> Tensor<&'a [u8], 2>,
+    ) -> Vec<(T, Tensor<Box<[u8]>, 2>)> {
+        let mut visited = HashMap::<&T, &Option<TPAction>>::new();
+        let mut ans = Vec::new();
+        for Edge { meta, external } in &self.0.edges {
+            let Some(External {
+                name,
+                item: TPTensor { act, val },
+            }) = external
+            else {
+                continue;
+            };
+            match visited.entry(val) {
+                Entry::Occupied(entry) => {
+                    assert!(*entry.get() == act, "{name} is split in different ways");
+                    continue;
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(act);
+                }
+            }
+
+            // 计算图中的形状以存储单元为单位，构造张量时需要的是数值的数量
+            let mut shape = meta.shape.iter().map(Dim::to_usize).collect::<Vec<_>>();
+            if let Some(last) = shape.last_mut() {
+                *last *= meta.dt.group_size()
+            }
+            let src = load(val);
+            let mut dst = Tensor::from_dim_slice(meta.dt, &shape)
+                .map(|len| vec![0u8; len].into_boxed_slice());
+            match act {
+                Some(TPAction { wt, dist }) => wt.move_data(*dist, dst.get_mut(), &src),
+                None => dst.get_mut().copy_from_slice(src.get()),
+            }
+            ans.push((val.clone(), dst))
+        }
+        ans
+    }
+}
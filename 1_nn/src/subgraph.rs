@@ -0,0 +1,158 @@
+use crate::{Edge, NNGraph};
+use graph::{GraphTopo, NodeRef, TopoNode};
+use std::ops::Range;
+
+impl<T: Clone> NNGraph<T> {
+    /// 提取序号在 `nodes` 范围内的节点构成独立的计算图，范围为空时返回 `None`
+    ///
+    /// 跨越切分边界的张量成为子图的全图输入输出，外部张量保留在子图中：
+    ///
+    /// ```rust
+    /// # use nn::{Dim, GraphBuilder, Linear, Mlp, Activation, TensorMeta, op, digit_layout::types};
+    /// let mut builder = GraphBuilder::default();
+    /// builder
+    ///     .register_op("linear", op::linear::Linear)
+    ///     .register_op("gelu", op::activation::GeLU);
+    /// let mlp = Mlp {
+    ///     up: Linear::new(types::F16, [32, 8], "up", None),
+    ///     act: Activation::GeLU,
+    ///     down: Linear::new(types::F16, [8, 32], "down", None),
+    /// };
+    /// let x = TensorMeta::new(types::F16, [Dim::from("n"), Dim::from(8)]);
+    /// let graph = builder.build(mlp, [x]).unwrap();
+    ///
+    /// // 只取激活和 ffn-down：激活的输入来自被切掉的 ffn-up，成为子图的输入
+    /// let sub = graph.subgraph(1..3).unwrap();
+    /// let topo = &sub.0.topo;
+    /// assert_eq!(topo.global_inputs().len(), 1);
+    /// assert_eq!(topo.global_outputs().len(), 1);
+    /// assert_eq!(sub.0.edges[topo.global_inputs().start].meta.shape[1], Dim::from(32));
+    /// // ffn-down 的权重作为外部张量保留
+    /// let externals = sub.0.edges.iter().filter_map(|e| e.external.as_ref());
+    /// assert_eq!(externals.map(|e| e.item).collect::<Vec<_>>(), ["down"]);
+    ///
+    /// // 只取 ffn-up：输出被后续节点使用，成为子图的输出
+    /// let sub = graph.subgraph_by_namespace("Ω.ffn-up").unwrap();
+    /// assert_eq!(sub.0.nodes.len(), 1);
+    /// assert_eq!(sub.0.topo.global_outputs().len(), 1);
+    ///
+    /// assert!(graph.subgraph(1..1).is_none());
+    /// assert!(graph.subgraph_by_namespace("Ω.attn").is_none());
+    /// ```
+    pub fn subgraph(&self, nodes: Range<usize>) -> Option<Self> {
+        assert!(nodes.end <= self.0.nodes.len());
+        self.extract(&nodes.collect::<Vec<_>>())
+    }
+
+    /// 提取名字在命名空间 `prefix`（如 `Ω.blk3`）之下的节点构成独立的计算图，
+    /// 没有节点在该命名空间之下时返回 `None`
+    pub fn subgraph_by_namespace(&self, prefix: &str) -> Option<Self> {
+        let selected = self
+            .0
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| {
+                node.name
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with(['.', ':']))
+            })
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        self.extract(&selected)
+    }
+
+    /// 提取 `selected` 中的节点（按节点序号升序）构成独立的计算图。
+    ///
+    /// 被选中的节点使用的、由其他节点产生或来自全图输入的张量成为子图的全图输入；
+    /// 被选中的节点产生的、被其他节点使用或作为全图输出的张量成为子图的全图输出；
+    /// 外部张量作为子图中第一个使用它的节点的局部张量保留。
+    /// 没有选中任何节点时返回 `None`。
+    pub(crate) fn extract(&self, selected: &[usize]) -> Option<Self> {
+        if selected.is_empty() {
+            return None;
+        }
+        let graph::Graph { topo, nodes, edges } = &self.0;
+        let topo_nodes = topo.iter().collect::<Vec<_>>();
+
+        let mut is_selected = vec![false; topo_nodes.len()];
+        for &i in selected {
+            is_selected[i] = true
+        }
+        // 标记产生每个张量的节点，以及需要从子图中导出的张量
+        let mut producer = vec![usize::MAX; edges.len()];
+        let mut export = vec![false; edges.len()];
+        for &i in topo.global_outputs() {
+            export[i] = true
+        }
+        for (i, NodeRef { inputs, outputs }) in topo_nodes.iter().enumerate() {
+            for j in outputs.clone() {
+                producer[j] = i
+            }
+            if !is_selected[i] {
+                for &j in *inputs {
+                    export[j] = true
+                }
+            }
+        }
+
+        let mut edge_map = vec![usize::MAX; edges.len()];
+        let mut new_edges = Vec::<Edge<T>>::new();
+        // 填入全图输入
+        for &i in selected {
+            for &j in topo_nodes[i].inputs {
+                let external = edges[j].external.is_some();
+                let inner = producer[j] != usize::MAX && is_selected[producer[j]];
+                if !external && !inner && edge_map[j] == usize::MAX {
+                    edge_map[j] = new_edges.len();
+                    new_edges.push(edges[j].clone())
+                }
+            }
+        }
+        let n_inputs = new_edges.len();
+        // 遍历节点
+        let mut topo_nodes_ = Vec::with_capacity(selected.len());
+        let mut connections = Vec::new();
+        let mut global_outputs = Vec::new();
+        for &i in selected {
+            let NodeRef { inputs, outputs } = &topo_nodes[i];
+            let mut n_local = 0;
+            for &j in *inputs {
+                if edge_map[j] == usize::MAX {
+                    // 未映射，应该是权重
+                    edge_map[j] = new_edges.len();
+                    new_edges.push(edges[j].clone());
+                    n_local += 1
+                }
+                connections.push(edge_map[j])
+            }
+            for j in outputs.clone() {
+                edge_map[j] = new_edges.len();
+                new_edges.push(edges[j].clone());
+                if export[j] {
+                    global_outputs.push(edge_map[j])
+                }
+            }
+            topo_nodes_.push(TopoNode {
+                n_local,
+                n_inputs: inputs.len(),
+                n_outputs: outputs.len(),
+            })
+        }
+        let n_outputs = global_outputs.len();
+        global_outputs.extend(connections);
+
+        Some(Self(graph::Graph {
+            topo: unsafe {
+                GraphTopo::from_raw_parts(
+                    n_inputs,
+                    n_outputs,
+                    global_outputs.into(),
+                    topo_nodes_.into(),
+                )
+            },
+            nodes: selected.iter().map(|&i| nodes[i].clone()).collect(),
+            edges: new_edges.into(),
+        }))
+    }
+}
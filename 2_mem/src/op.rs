@@ -33,6 +33,37 @@ pub(crate) fn split<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) {
     }
 }
 
+pub(crate) fn slice<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) {
+    let NodeRef { inputs, outputs } = topo;
+    // slice 应该只有一个输入
+    let &[input] = inputs else { unreachable!() };
+    let input = edges[input].clone();
+    // 提取属性
+    let Some(Arg::Dict(arg)) = &node.value.arg else {
+        unreachable!()
+    };
+    let axis = arg["axis"].to_usize();
+    let start = arg["start"].to_usize();
+    let len = arg["len"].to_usize();
+    let step = arg.get("step").map_or(1, Arg::to_usize);
+    // 计算步长变换
+    assert_eq!(outputs.len(), 1); // slice 应该只有一个输出
+    for output in outputs {
+        let output = &mut edges[output];
+        // 暂时不支持 output 是外部的，因为外部 output 需要添加 rearrange kernel
+        assert!(matches!(&**output.get(), Info::Internal(_)));
+        // 用 slice 实现，并替换原来的边
+        *output = input
+            .clone()
+            .transform(|layout| layout.slice(axis, start, step as _, len));
+    }
+    // 算子擦除
+    node.value = Operator {
+        name: "empty".to_string(),
+        arg: None,
+    }
+}
+
 pub(crate) fn tile<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) {
     let NodeRef { inputs, outputs } = topo;
     // tile 应该只有一个输入
@@ -141,3 +172,51 @@ pub(crate) fn concat<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) {
         arg: None,
     }
 }
+
+pub(crate) fn broadcast<T>(node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) {
+    let NodeRef { inputs, outputs } = topo;
+    // broadcast 应该只有一个输入
+    let &[input] = inputs else { unreachable!() };
+    let input = edges[input].clone();
+    // 计算步长变换
+    assert_eq!(outputs.len(), 1); // broadcast 应该只有一个输出
+    for output in outputs {
+        let output = &mut edges[output];
+        // 暂时不支持 output 是外部的，因为外部 output 需要添加 rearrange kernel
+        assert!(matches!(&**output.get(), Info::Internal(_)));
+        let shape = output.shape().to_vec();
+        // 在前面补足长度为 1 的维度，再将长度为 1 的维度用步长为 0 的布局扩展
+        let mut tile = vec![1; shape.len() - input.shape().len()];
+        tile.push(input.shape()[0]);
+        *output = input.clone().transform(|layout| {
+            let mut layout = layout.tile_be(0, &tile);
+            for (axis, &d) in shape.iter().enumerate() {
+                if layout.shape()[axis] != d {
+                    layout = layout.broadcast(axis, d)
+                }
+            }
+            layout
+        });
+    }
+    // 算子擦除
+    node.value = Operator {
+        name: "empty".to_string(),
+        arg: None,
+    }
+}
+
+pub(crate) fn recv<T>(_node: &mut Node, topo: NodeRef, edges: &mut [Edge<T>]) {
+    let NodeRef { inputs, outputs } = topo;
+    // recv 应该只有一个输入
+    let &[input] = inputs else { unreachable!() };
+    let input = edges[input].clone();
+    assert_eq!(outputs.len(), 1); // recv 应该只有一个输出
+    for output in outputs {
+        let output = &mut edges[output];
+        // 暂时不支持 output 是外部的，因为外部 output 需要添加 rearrange kernel
+        assert!(matches!(&**output.get(), Info::Internal(_)));
+        // 数据接收到输入中，输出与输入共享存储
+        *output = input.clone()
+    }
+    // 算子需要执行，不擦除
+}
@@ -1,20 +1,30 @@
+mod transport;
+
 use arg::Arg;
-use graph::{Named, NodeRef};
+use graph::{Encode, Encoder, Named, NodeRef};
 use std::iter::zip;
 
 pub use tensor::Tensor;
+pub use transport::{LocalTransport, RankLayout, Transport};
 
 #[repr(transparent)]
 pub struct Graph<T>(pub graph::Graph<Node, Tensor<T, 2>>);
 
 pub type Node = Named<Operator>;
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Operator {
     pub name: String,
     pub arg: Option<Arg>,
 }
 
+impl Encode for Operator {
+    fn encode(&self, enc: &mut Encoder) {
+        self.name.encode(enc);
+        self.arg.encode(enc)
+    }
+}
+
 pub struct Exec<T> {
     pub node: Node,
     pub inputs: Box<[Tensor<T, 2>]>,
@@ -36,3 +46,87 @@ impl<T: Clone> Graph<T> {
             .collect()
     }
 }
+
+impl<T> Exec<T> {
+    /// 通过 `transport` 执行 `send`/`recv` 节点并返回 `true`，其他节点返回 `false`。
+    ///
+    /// 对方为参数指定的阶段中张量并行 rank 与本端点相同的端点。
+    /// `send` 发送唯一的输入，`recv` 写入唯一的输入，其输出与输入共享存储。
+    ///
+    /// ```rust
+    /// use arg::Arg;
+    /// use exec::{Exec, LocalTransport, Operator, RankLayout, Tensor, Transport};
+    /// use graph::Named;
+    /// use tensor::digit_layout::types;
+    ///
+    /// // 2 个流水线阶段 × 2 路张量并行，阶段 0 把隐状态发送给阶段 1
+    /// let layout = RankLayout { stages: 2, tp: 2 };
+    /// let handles = LocalTransport::group(layout.size())
+    ///     .into_iter()
+    ///     .map(|t| {
+    ///         std::thread::spawn(move || {
+    ///             let (stage, r) = layout.locate(t.rank());
+    ///             let tensor = Tensor::from_dim_slice(types::U8, [2, 4]);
+    ///             let mut x = vec![0u8; *tensor.get()];
+    ///             let (name, peer) = if stage == 0 {
+    ///                 x.fill(r as u8 + 1);
+    ///                 ("send", 1)
+    ///             } else {
+    ///                 ("recv", 0)
+    ///             };
+    ///             let tensor = tensor.map(|_| x.as_mut_ptr());
+    ///             let exec = Exec {
+    ///                 node: Named {
+    ///                     name: format!("Ω.{name}"),
+    ///                     value: Operator {
+    ///                         name: name.into(),
+    ///                         arg: Some(Arg::int(peer)),
+    ///                     },
+    ///                 },
+    ///                 inputs: [tensor.clone()].into(),
+    ///                 outputs: if stage == 0 { [].into() } else { [tensor].into() },
+    ///             };
+    ///             assert!(unsafe { exec.launch_p2p(&t, layout, |&ptr| ptr) });
+    ///             x
+    ///         })
+    ///     })
+    ///     .collect::<Vec<_>>();
+    /// for (rank, h) in handles.into_iter().enumerate() {
+    ///     let (_, r) = layout.locate(rank);
+    ///     assert!(h.join().unwrap().iter().all(|&b| b == r as u8 + 1))
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// `ptr` 返回的地址指向张量的连续存储，`recv` 期间这块存储不被其他引用访问。
+    pub unsafe fn launch_p2p(
+        &self,
+        transport: &impl Transport,
+        layout: RankLayout,
+        ptr: impl Fn(&T) -> *mut u8,
+    ) -> bool {
+        let Self { node, inputs, .. } = self;
+        let Operator { name, arg } = &node.value;
+        if !matches!(&**name, "send" | "recv") {
+            return false;
+        }
+
+        let Some(Arg::Int(stage)) = arg else {
+            panic!("{name} requires peer stage")
+        };
+        let [x] = &**inputs else {
+            panic!("{name} requires exactly one input")
+        };
+        let (_, tp_rank) = layout.locate(transport.rank());
+        let peer = layout.rank(*stage as _, tp_rank);
+        let len = x.shape().iter().product::<usize>() * x.dt().nbytes();
+        let ptr = ptr(x.get());
+        match &**name {
+            "send" => transport.send(peer, unsafe { std::slice::from_raw_parts(ptr, len) }),
+            "recv" => transport.recv(peer, unsafe { std::slice::from_raw_parts_mut(ptr, len) }),
+            _ => unreachable!(),
+        }
+        true
+    }
+}
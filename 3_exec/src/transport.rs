@@ -0,0 +1,132 @@
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+/// 点对点通信，实现 `send`/`recv` 算子。
+///
+/// 流水线并行和张量并行组合时，全局 rank 由 [`RankLayout`] 确定。
+pub trait Transport {
+    /// 本端点的全局 rank
+    fn rank(&self) -> usize;
+    /// 把 `data` 发送给 `peer`，不等待对方接收
+    fn send(&self, peer: usize, data: &[u8]);
+    /// 从 `peer` 接收数据填满 `buf`，阻塞直到数据到达
+    fn recv(&self, peer: usize, buf: &mut [u8]);
+}
+
+/// 流水线并行和张量并行组合时的 rank 布局，阶段 `s` 中张量并行 rank 为 `r` 的分片的全局 rank 为 `s * tp + r`。
+///
+/// ```rust
+/// use exec::RankLayout;
+///
+/// let layout = RankLayout { stages: 2, tp: 2 };
+/// assert_eq!(layout.size(), 4);
+/// assert_eq!(layout.rank(1, 0), 2);
+/// assert_eq!(layout.locate(3), (1, 1));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct RankLayout {
+    /// 流水线阶段数
+    pub stages: usize,
+    /// 每个阶段的张量并行路数
+    pub tp: usize,
+}
+
+impl RankLayout {
+    /// 全局 rank 总数
+    pub const fn size(&self) -> usize {
+        self.stages * self.tp
+    }
+
+    /// 阶段 `stage` 中张量并行 rank 为 `tp_rank` 的分片的全局 rank
+    pub const fn rank(&self, stage: usize, tp_rank: usize) -> usize {
+        assert!(stage < self.stages && tp_rank < self.tp);
+        stage * self.tp + tp_rank
+    }
+
+    /// 全局 rank 对应的阶段和张量并行 rank
+    pub const fn locate(&self, rank: usize) -> (usize, usize) {
+        assert!(rank < self.size());
+        (rank / self.tp, rank % self.tp)
+    }
+}
+
+/// 用通道模拟的本地多线程通信，每个线程持有一个端点，同一对端点之间的消息保序。
+///
+/// ```rust
+/// use exec::{LocalTransport, RankLayout, Transport};
+///
+/// // 2 个流水线阶段 × 2 路张量并行
+/// let layout = RankLayout { stages: 2, tp: 2 };
+/// let handles = LocalTransport::group(layout.size())
+///     .into_iter()
+///     .map(|t| {
+///         std::thread::spawn(move || {
+///             let (stage, r) = layout.locate(t.rank());
+///             let mut x = [0u8; 4];
+///             if stage == 0 {
+///                 x.fill(r as u8 + 1);
+///                 t.send(layout.rank(stage + 1, r), &x)
+///             } else {
+///                 t.recv(layout.rank(stage - 1, r), &mut x)
+///             }
+///             x
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// let x = handles.into_iter().map(|h| h.join().unwrap()).collect::<Vec<_>>();
+/// assert_eq!(x, [[1; 4], [2; 4], [1; 4], [2; 4]]);
+/// ```
+pub struct LocalTransport {
+    rank: usize,
+    /// 发往每个端点的通道
+    tx: Box<[Sender<Box<[u8]>>]>,
+    /// 来自每个端点的通道
+    rx: Box<[Receiver<Box<[u8]>>]>,
+}
+
+impl LocalTransport {
+    /// 创建 `n` 个两两连通的端点，第 `i` 个端点的 rank 为 `i`
+    pub fn group(n: usize) -> Vec<Self> {
+        let mut tx = (0..n).map(|_| Vec::with_capacity(n)).collect::<Vec<_>>();
+        let mut rx = (0..n).map(|_| Vec::with_capacity(n)).collect::<Vec<_>>();
+        for tx in &mut tx {
+            for rx in &mut rx {
+                let (sender, receiver) = channel();
+                tx.push(sender);
+                rx.push(receiver)
+            }
+        }
+        tx.into_iter()
+            .zip(rx)
+            .enumerate()
+            .map(|(rank, (tx, rx))| Self {
+                rank,
+                tx: tx.into(),
+                rx: rx.into(),
+            })
+            .collect()
+    }
+}
+
+impl Transport for LocalTransport {
+    fn rank(&self) -> usize {
+        self.rank
+    }
+
+    fn send(&self, peer: usize, data: &[u8]) {
+        self.tx[peer]
+            .send(data.into())
+            .unwrap_or_else(|_| panic!("rank {peer} disconnected"))
+    }
+
+    fn recv(&self, peer: usize, buf: &mut [u8]) {
+        let data = self.rx[peer]
+            .recv()
+            .unwrap_or_else(|_| panic!("rank {peer} disconnected"));
+        assert_eq!(
+            data.len(),
+            buf.len(),
+            "message size mismatch from rank {peer}"
+        );
+        buf.copy_from_slice(&data)
+    }
+}
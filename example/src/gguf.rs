@@ -1,6 +1,6 @@
 ﻿use crate::blob::Data;
 use ggus::{
-    GENERAL_ALIGNMENT, GGuf, GGufError, GGufFileName, GGufMetaDataValueType, GGufMetaKV,
+    GENERAL_ALIGNMENT, GGmlType, GGuf, GGufError, GGufFileName, GGufMetaDataValueType, GGufMetaKV,
     GGufMetaMap,
 };
 use memmap2::Mmap;
@@ -43,6 +43,8 @@ pub struct GGufModel<'a> {
     pub meta_kvs: HashMap<&'a str, GGufMetaKV<'a>>,
     /// 张量。
     pub tensors: HashMap<&'a str, Tensor<Data<'a>, 2>>,
+    /// 文件中张量的存储类型，不包括运行时生成的张量。
+    pub types: HashMap<&'a str, GGmlType>,
 }
 
 impl<'a> GGufModel<'a> {
@@ -51,6 +53,7 @@ impl<'a> GGufModel<'a> {
         let mut ans = Self {
             meta_kvs: Default::default(),
             tensors: Default::default(),
+            types: Default::default(),
         };
         thread::scope(|s| {
             for (i, thread) in files
@@ -86,6 +89,7 @@ impl<'a> GGufModel<'a> {
                 Occupied(_) => return Err(GGufError::DuplicateTensorName(name.into())),
                 Vacant(vacant) => {
                     let t = t.to_info();
+                    self.types.insert(name, t.ty());
                     let ty = t.ty().to_digit_layout();
                     let shape = t
                         .shape()
@@ -135,6 +139,20 @@ mod macros {
                 Err(e) => panic!("failed to read meta: {e:?}"),
             }
         };
+        ($gguf:expr => (f32) $key:expr; $default:expr) => {
+            match $gguf.get_f32($key) {
+                Ok(val) => val,
+                Err(ggus::GGufMetaError::NotExist) => $default,
+                Err(e) => panic!("failed to read meta: {e:?}"),
+            }
+        };
+        ($gguf:expr => (str) $key:expr; $default:expr) => {
+            match $gguf.get_str($key) {
+                Ok(val) => val,
+                Err(ggus::GGufMetaError::NotExist) => $default,
+                Err(e) => panic!("failed to read meta: {e:?}"),
+            }
+        };
     }
     #[macro_export]
     macro_rules! tensor {
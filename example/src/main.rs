@@ -1,80 +1,54 @@
 mod blob;
 mod gguf;
 mod model;
+mod shard;
 
 use gguf::{GGufModel, map_files};
-use ggus::ggml_quants::digit_layout::types;
-use nn::{Dim, Exec, GraphBuilder, Node, OpInfo, TensorMeta, op};
-use std::{collections::BTreeSet, iter::zip, time::Instant};
+use nn::{Distribution, Exec, GraphBuilder, Node, OpInfo, Segment, op};
+use std::{
+    ffi::{OsStr, OsString},
+    fs,
+    iter::zip,
+    path::PathBuf,
+    time::Instant,
+};
 
 // cargo run --release -- ../TinyStory-5M-v0.0-F32.gguf
+// cargo run --release -- shard ../TinyStory-5M-v0.0-F32.gguf 2 ../tp2
+// cargo run --release -- shard ../TinyStory-5M-v0.0-F32.gguf 2,1,1 ../tp3
 fn main() {
+    let mut args = std::env::args_os().skip(1);
+    let Some(path) = args.next() else { usage() };
+    if path == "shard" {
+        return shard(args);
+    }
+
     let mut timer = Timer::default();
 
-    let path = std::env::args_os().nth(1).unwrap();
     let maps = map_files(path);
     let mut gguf = GGufModel::read(maps.iter().map(|x| &**x));
     let model = model::init(&mut gguf);
+    // 张量并行的分片按记录的切分方式构造，权重不需要再切分
+    let dist = shard::distribution(&gguf).unwrap_or(Distribution::MONO);
     timer.push("init");
 
     // 构造计算图
-    let graph = GraphBuilder::default()
-        .register_op("embedding", op::embedding::Embedding)
-        .register_op("rms-norm", op::normalization::RmsNorm)
-        .register_op("layer-norm", op::normalization::LayerNorm)
-        .register_op("attention", op::attention::Attention)
-        .register_op("split", op::split::Split)
-        .register_op("tile", op::tile::Tile)
-        .register_op("merge", op::merge::Merge)
-        .register_op("swiglu", op::activation::SwiGLU)
-        .register_op("gelu", op::activation::GeLU)
-        .register_op("linear", op::linear::Linear)
-        .register_op("rope", op::rope::Rope)
-        .register_op("concat", op::concat::Concat)
-        .build(
-            model,
-            [
-                TensorMeta::new(types::U32, [Dim::from("n_tok")]),
-                TensorMeta::new(types::U32, [Dim::from("n_tok")]),
-                TensorMeta::new(types::U32, [Dim::from("n_out")]),
-            ],
-        )
-        .unwrap();
+    let graph = model.build(&builder(), dist).unwrap();
     timer.push("build");
     // 动态性分析
-    let mut start: Option<(String, usize)> = None;
-    let mut variables = BTreeSet::new();
-    for (i, (topo, node)) in zip(graph.0.topo.iter(), &graph.0.nodes).enumerate() {
-        let mut vars = BTreeSet::new();
-        for i in topo.inputs.iter().cloned().chain(topo.outputs) {
-            for d in graph.0.edges[i].meta.shape.iter() {
-                d.append_variables(&mut vars)
-            }
-        }
-        match start.as_ref() {
-            Some((name, start_)) => {
-                if variables != vars {
-                    println!(
-                        "{start_:>3}..{i:>3} {name:>30}..{:<30} {variables:?}",
-                        node.name
-                    );
-                    start = Some((node.name.clone(), i));
-                    variables = vars;
-                }
-            }
-            None => {
-                start = Some((node.name.clone(), i));
-                variables = vars;
-            }
-        }
-    }
-    if let Some((name, start)) = start {
-        println!("{start:>3}..    {name:>30}..{:<30} {variables:?}", "")
+    for Segment { nodes, variables } in graph.dynamism() {
+        println!(
+            "{:>3}..{:>3} {:>30}..{:<30} {variables:?}",
+            nodes.start,
+            nodes.end,
+            graph.0.nodes[nodes.start].name,
+            graph.0.nodes[nodes.end - 1].name,
+        )
     }
     println!();
     // 锁定形状
     let graph = graph.lower(&[("n_tok", 5), ("n_out", 1)].into(), |t| {
-        gguf.tensors[&*t].as_ref()
+        gguf.tensors[&*t.val].as_ref()
     });
     timer.push("fix shape");
     // 分配空间
@@ -101,6 +75,118 @@ fn main() {
     }
 }
 
+fn builder() -> GraphBuilder {
+    let mut builder = GraphBuilder::default();
+    builder
+        .register_op("embedding", op::embedding::Embedding)
+        .register_op("masked-embedding", op::embedding::MaskedEmbedding)
+        .register_op("rms-norm", op::normalization::RmsNorm)
+        .register_op("layer-norm", op::normalization::LayerNorm)
+        .register_op("attention", op::attention::Attention)
+        .register_op("mla", op::mla::Mla)
+        .register_op("rope", op::rope::Rope)
+        .register_op("mrope", op::mrope::Mrope)
+        .register_op("linear", op::linear::Linear)
+        .register_op("quant-linear", op::linear::QuantLinear)
+        .register_op("matmul", op::matmul::MatMul)
+        .register_op("conv", op::conv::Conv)
+        .register_op("swiglu", op::activation::SwiGLU)
+        .register_op("geglu", op::activation::GeGLU)
+        .register_op("reglu", op::activation::ReGLU)
+        .register_op("silu", op::activation::SiLU)
+        .register_op("gelu", op::activation::GeLU)
+        .register_op("gelu-tanh", op::activation::GeLUTanh)
+        .register_op("quick-gelu", op::activation::QuickGeLU)
+        .register_op("relu", op::activation::ReLU)
+        .register_op("squared-relu", op::activation::SquaredReLU)
+        .register_op("tanh", op::activation::Tanh)
+        .register_op("add", op::element_wise::Add)
+        .register_op("sub", op::element_wise::Sub)
+        .register_op("mul", op::element_wise::Mul)
+        .register_op("div", op::element_wise::Div)
+        .register_op("scale", op::element_wise::Scale)
+        .register_op("softcap", op::element_wise::Softcap)
+        .register_op("cast", op::element_wise::Cast)
+        .register_op("reduce-sum", op::reduce::ReduceSum)
+        .register_op("reduce-mean", op::reduce::ReduceMean)
+        .register_op("reduce-max", op::reduce::ReduceMax)
+        .register_op("reduce-min", op::reduce::ReduceMin)
+        .register_op("argmax", op::reduce::ArgMax)
+        .register_op("gather", op::gather::Gather)
+        .register_op("index-select", op::gather::IndexSelect)
+        .register_op("scatter", op::gather::Scatter)
+        .register_op("index-put", op::gather::IndexPut)
+        .register_op("split", op::split::Split)
+        .register_op("tile", op::tile::Tile)
+        .register_op("merge", op::merge::Merge)
+        .register_op("transpose", op::transpose::Transpose)
+        .register_op("concat", op::concat::Concat)
+        .register_op("slice", op::slice::Slice)
+        .register_op("pad", op::pad::Pad)
+        .register_op("broadcast", op::broadcast::Broadcast)
+        .register_op("all-reduce", op::all_reduce::AllReduce)
+        .register_op("all-gather", op::all_gather::AllGather)
+        .register_op("reduce-scatter", op::reduce_scatter::ReduceScatter)
+        .register_op("send", op::p2p::Send)
+        .register_op("recv", op::p2p::Recv);
+    builder
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "\
+Usage: example <model.gguf>
+       example shard <model.gguf> <ranks | parts,...> <dir>
+
+  ranks      均分为 ranks 个 rank
+  parts,...  每个 rank 的份数，如 2,1,1"
+    );
+    std::process::exit(1)
+}
+
+/// 按张量并行切分模型，第 `i` 个 rank 的权重写入 `<dir>/rank<i>.gguf`
+fn shard(mut args: impl Iterator<Item = OsString>) {
+    let (Some(path), Some(parts), Some(dir), None) =
+        (args.next(), args.next(), args.next(), args.next())
+    else {
+        usage()
+    };
+    let Some(parts) = parse_parts(&parts) else {
+        usage()
+    };
+    let dists = Distribution::partition(&parts);
+    if !Distribution::is_partition(&dists) {
+        usage()
+    }
+    let dir = PathBuf::from(dir);
+
+    let maps = map_files(path);
+    let mut gguf = GGufModel::read(maps.iter().map(|x| &**x));
+    let model = model::init(&mut gguf);
+    let builder = builder();
+
+    let graphs = model.build_parallel(&builder, &dists).unwrap();
+    fs::create_dir_all(&dir).unwrap();
+    for (i, (dist, graph)) in zip(dists, graphs).enumerate() {
+        let path = dir.join(format!("rank{i}.gguf"));
+        shard::write_shard(&gguf, dist, &graph, &path).unwrap();
+        println!("{}", path.display())
+    }
+}
+
+/// 解析各 rank 的份数，单个数 `n` 表示均分为 `n` 个 rank
+fn parse_parts(arg: &OsStr) -> Option<Vec<usize>> {
+    let arg = arg.to_str()?;
+    let parts = if arg.contains(',') {
+        arg.split(',')
+            .map(|part| part.trim().parse().ok())
+            .collect::<Option<Vec<usize>>>()?
+    } else {
+        vec![1; arg.parse().ok()?]
+    };
+    (!parts.is_empty() && !parts.contains(&0)).then_some(parts)
+}
+
 #[derive(Default)]
 #[repr(transparent)]
 struct Timer(Vec<(String, Instant)>);
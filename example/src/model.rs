@@ -4,16 +4,88 @@
     meta,
 };
 use ggus::GGufMetaMapExt;
-use nn::{MRoPE, Tensor};
-use tensor::digit_layout::types;
+use nn::{
+    Dim, Distribution, GraphBuilder, MRoPE, NNError, NNGraph, RopeScaling, TPTensor, Tensor,
+    TensorMeta, digit_layout::types,
+};
+
+/// 按架构构造的模型
+#[derive(Clone)]
+pub enum Model {
+    LLaMA(nn::LLaMA<String>),
+    GPT2(nn::GPT2<String>),
+    Bert(nn::BertEncoder<String>),
+}
+
+pub fn init(gguf: &mut GGufModel) -> Model {
+    match meta![gguf => general_architecture] {
+        "llama" | "qwen2" | "qwen3" => Model::LLaMA(llama(gguf)),
+        "gpt2" => Model::GPT2(gpt2(gguf)),
+        "bert" => Model::Bert(bert(gguf)),
+        arch => panic!("unsupported arch {arch}"),
+    }
+}
+
+impl Model {
+    /// 按张量并行的切分方式，用模型对应的输入构造计算图
+    pub fn build(
+        self,
+        builder: &GraphBuilder,
+        dist: Distribution,
+    ) -> Result<NNGraph<TPTensor<String>>, NNError> {
+        let inputs = self.inputs();
+        match self {
+            Self::LLaMA(model) => builder.build(model.tensor_parallel(dist), inputs),
+            Self::GPT2(model) => builder.build(model.tensor_parallel(dist), inputs),
+            Self::Bert(model) => builder.build(model.tensor_parallel(dist), inputs),
+        }
+    }
+
+    /// 为张量并行的每个 rank 构造计算图
+    pub fn build_parallel(
+        self,
+        builder: &GraphBuilder,
+        dists: &[Distribution],
+    ) -> Result<Vec<NNGraph<TPTensor<String>>>, NNError> {
+        let inputs = self.inputs();
+        match self {
+            Self::LLaMA(model) => {
+                builder.build_parallel(dists, |dist| model.clone().tensor_parallel(dist), &inputs)
+            }
+            Self::GPT2(model) => {
+                builder.build_parallel(dists, |dist| model.clone().tensor_parallel(dist), &inputs)
+            }
+            Self::Bert(model) => {
+                builder.build_parallel(dists, |dist| model.clone().tensor_parallel(dist), &inputs)
+            }
+        }
+    }
+
+    /// 计算图的输入，`n_out` 为输出的行数，对于 BERT 是序列数
+    fn inputs(&self) -> Vec<TensorMeta> {
+        let tokens = || TensorMeta::new(types::U32, [Dim::from("n_tok")]);
+        let out = || TensorMeta::new(types::U32, [Dim::from("n_out")]);
+        match self {
+            Self::LLaMA(_) | Self::GPT2(_) => vec![tokens(), tokens(), out()],
+            Self::Bert(bert) => {
+                let mut inputs = vec![tokens(), tokens()];
+                if bert.token_types.is_some() {
+                    inputs.push(tokens())
+                }
+                if bert.pooler.is_some() {
+                    inputs.push(out())
+                }
+                inputs
+            }
+        }
+    }
+}
 
-pub fn init(gguf: &mut GGufModel) -> nn::LLaMA<String> {
+fn llama(gguf: &mut GGufModel) -> nn::LLaMA<String> {
     let arch = meta![gguf => general_architecture];
     let dt_bias = match arch {
-        "llama" => None,
         "qwen2" => Some(gguf.tensors["blk.0.attn_qkv.bias"].dt()),
-        "qwen3" => None,
-        arch => panic!("unsupported arch {arch}"),
+        _ => None,
     };
 
     let nvoc = meta![gguf => tokenizer_ggml_tokens].len();
@@ -22,20 +94,27 @@ pub fn init(gguf: &mut GGufModel) -> nn::LLaMA<String> {
     let d = meta![gguf => llm_embedding_length];
     let nh = meta![gguf => llm_attention_head_count];
     let nkvh = meta![gguf => llm_attention_head_count_kv; nh];
+    // 张量并行的分片中权重已经切分，优先从元数据读取头维度
     let dh = match arch {
-        "qwen3" => gguf.tensors["blk.0.attn_qkv.weight"].shape()[0]
-            .checked_div(nh + nkvh + nkvh)
-            .unwrap(),
-        _ => meta![gguf => llm_rope_dimension_count; d / nh],
+        "qwen3" => meta![gguf => (usize) &format!("{arch}.attention.key_length");
+            gguf.tensors["blk.0.attn_qkv.weight"].shape()[0]
+                .checked_div(nh + nkvh + nkvh)
+                .unwrap()],
+        _ => d / nh,
     };
     let di = meta![gguf => llm_feed_forward_length];
     let epsilon = meta![gguf => llm_attention_layer_norm_rms_epsilon; 1e-5];
     let dt_embd = gguf.tensors["token_embd.weight"].dt();
     let dt_norm = gguf.tensors["output_norm.weight"].dt();
     let dt_linear = gguf.tensors["blk.0.attn_qkv.weight"].dt();
-    let theta = meta![gguf => llm_rope_freq_base; 1e4];
+    let rope = ::nn::RopeTable {
+        nctx,
+        dim: meta![gguf => llm_rope_dimension_count; dh],
+        theta: meta![gguf => llm_rope_freq_base; 1e4],
+        scaling: rope_scaling(gguf, arch, nctx),
+    };
 
-    let [sin, cos] = build_sin_cos(nctx, dh, theta);
+    let [sin, cos] = rope.build().map(to_data);
     gguf.tensors.insert("sin_table", sin);
     gguf.tensors.insert("cos_table", cos);
 
@@ -49,6 +128,8 @@ pub fn init(gguf: &mut GGufModel) -> nn::LLaMA<String> {
             },
             wpe: None,
             img_info: None,
+            scale: None,
+            vocab_parallel: None,
         },
         blks: (0..nblk)
             .map(|iblk| {
@@ -59,6 +140,7 @@ pub fn init(gguf: &mut GGufModel) -> nn::LLaMA<String> {
                         items: ::nn::NormType::RmsNorm {
                             dt: dt_norm,
                             scale: format!("blk.{iblk}.attn_norm.weight"),
+                            offset: 0.,
                         },
                     },
                     ::nn::Attention {
@@ -80,6 +162,7 @@ pub fn init(gguf: &mut GGufModel) -> nn::LLaMA<String> {
                                 items: ::nn::NormType::RmsNorm {
                                     dt: dt_norm,
                                     scale: format!("blk.{iblk}.attn_q_norm.weight"),
+                                    offset: 0.,
                                 },
                             })
                         } else {
@@ -95,6 +178,7 @@ pub fn init(gguf: &mut GGufModel) -> nn::LLaMA<String> {
                                 items: ::nn::NormType::RmsNorm {
                                     dt: dt_norm,
                                     scale: format!("blk.{iblk}.attn_k_norm.weight"),
+                                    offset: 0.,
                                 },
                             })
                         } else {
@@ -103,9 +187,15 @@ pub fn init(gguf: &mut GGufModel) -> nn::LLaMA<String> {
                         rope: Some(::nn::RoPE {
                             multimodal: MRoPE::None,
                             nctx,
+                            dim: rope.dim,
                             sin: "sin_table".into(),
                             cos: "cos_table".into(),
                         }),
+                        causal: true,
+                        window: None,
+                        softcap: None,
+                        alibi: None,
+                        sinks: None,
                         output: ::nn::Linear::new(
                             dt_linear,
                             [d, nh * dh],
@@ -119,6 +209,7 @@ pub fn init(gguf: &mut GGufModel) -> nn::LLaMA<String> {
                         items: ::nn::NormType::RmsNorm {
                             dt: dt_norm,
                             scale: format!("blk.{iblk}.ffn_norm.weight"),
+                            offset: 0.,
                         },
                     },
                     ::nn::Mlp {
@@ -146,6 +237,7 @@ pub fn init(gguf: &mut GGufModel) -> nn::LLaMA<String> {
                 items: ::nn::NormType::RmsNorm {
                     dt: dt_norm,
                     scale: "output_norm.weight".into(),
+                    offset: 0.,
                 },
             },
             lm_head: ::nn::Linear::new(
@@ -159,43 +251,206 @@ pub fn init(gguf: &mut GGufModel) -> nn::LLaMA<String> {
                 .into(),
                 None,
             ),
+            softcap: None,
+            vocab_parallel: None,
         }),
     }
 }
 
-/// 构造 sin cos 表张量
-fn build_sin_cos<'a, const N: usize>(
-    nctx: usize,
-    dh: usize,
-    theta: f32,
-) -> [Tensor<Data<'a>, N>; 2] {
-    let ty = types::F32;
-    let mut sin = Blob::new(nctx * dh / 2 * ty.nbytes());
-    let mut cos = Blob::new(nctx * dh / 2 * ty.nbytes());
-
-    {
-        let ([], sin, []) = (unsafe { sin.align_to_mut::<f32>() }) else {
-            unreachable!()
-        };
-        let ([], cos, []) = (unsafe { cos.align_to_mut::<f32>() }) else {
-            unreachable!()
-        };
-        for pos in 0..nctx {
-            for i in 0..dh / 2 {
-                let theta = theta.powf(-((2 * i) as f32 / dh as f32));
-                let freq = pos as f32 * theta;
-                let (sin_, cos_) = freq.sin_cos();
-                sin[pos * dh / 2 + i] = sin_;
-                cos[pos * dh / 2 + i] = cos_;
-            }
-        }
+fn gpt2(gguf: &GGufModel) -> nn::GPT2<String> {
+    let nvoc = meta![gguf => tokenizer_ggml_tokens].len();
+    let nblk = meta![gguf => llm_block_count];
+    let d = meta![gguf => llm_embedding_length];
+    let nh = meta![gguf => llm_attention_head_count];
+    let di = meta![gguf => llm_feed_forward_length];
+    let epsilon = meta![gguf => (f32) "gpt2.attention.layer_norm_epsilon"; 1e-5];
+
+    ::nn::GPT2 {
+        embedding: learned_embedding(gguf, nvoc, d),
+        blks: (0..nblk)
+            .map(|iblk| {
+                ::nn::TransformerBlk::new(
+                    layer_norm(gguf, d, epsilon, &format!("blk.{iblk}.attn_norm")),
+                    biased_attention(gguf, iblk, d, nh, true),
+                    layer_norm(gguf, d, epsilon, &format!("blk.{iblk}.ffn_norm")),
+                    gelu_mlp(gguf, iblk, d, di),
+                )
+            })
+            .collect(),
+        output_head: Some(::nn::OutputHead {
+            out_norm: layer_norm(gguf, d, epsilon, "output_norm"),
+            lm_head: ::nn::Linear::new(
+                gguf.tensors["token_embd.weight"].dt(),
+                [nvoc, d],
+                if gguf.tensors.contains_key("output.weight") {
+                    "output.weight"
+                } else {
+                    "token_embd.weight"
+                }
+                .into(),
+                None,
+            ),
+            softcap: None,
+            vocab_parallel: None,
+        }),
     }
+}
 
-    let tensor = |data: Blob| {
-        Tensor::from_dim_slice(ty, [nctx, dh / 2]).map(|len| {
-            assert_eq!(len, data.len());
-            data.into()
-        })
-    };
-    [tensor(sin), tensor(cos)]
+fn bert(gguf: &GGufModel) -> nn::BertEncoder<String> {
+    let nvoc = meta![gguf => tokenizer_ggml_tokens].len();
+    let nblk = meta![gguf => llm_block_count];
+    let d = meta![gguf => llm_embedding_length];
+    let nh = meta![gguf => llm_attention_head_count];
+    let di = meta![gguf => llm_feed_forward_length];
+    let epsilon = meta![gguf => (f32) "bert.attention.layer_norm_epsilon"; 1e-12];
+
+    ::nn::BertEncoder {
+        embedding: learned_embedding(gguf, nvoc, d),
+        token_types: gguf
+            .tensors
+            .get("token_types.weight")
+            .map(|table| ::nn::Table {
+                row: table.shape()[0],
+                weight: "token_types.weight".into(),
+            }),
+        embd_norm: layer_norm(gguf, d, epsilon, "token_embd_norm"),
+        blks: (0..nblk)
+            .map(|iblk| ::nn::TransformerBlk {
+                norm: ::nn::NormPlacement::Post,
+                ..::nn::TransformerBlk::new(
+                    layer_norm(gguf, d, epsilon, &format!("blk.{iblk}.attn_output_norm")),
+                    biased_attention(gguf, iblk, d, nh, false),
+                    layer_norm(gguf, d, epsilon, &format!("blk.{iblk}.layer_output_norm")),
+                    gelu_mlp(gguf, iblk, d, di),
+                )
+            })
+            .collect(),
+        pooler: gguf
+            .tensors
+            .contains_key("cls.weight")
+            .then(|| linear(gguf, [d, d], "cls")),
+    }
+}
+
+/// 带学习的位置编码表的词嵌入
+fn learned_embedding(gguf: &GGufModel, nvoc: usize, d: usize) -> nn::Embedding<String> {
+    ::nn::Embedding {
+        dt: gguf.tensors["token_embd.weight"].dt(),
+        d,
+        wte: ::nn::Table {
+            row: nvoc,
+            weight: "token_embd.weight".into(),
+        },
+        wpe: Some(::nn::Table {
+            row: gguf.tensors["position_embd.weight"].shape()[0],
+            weight: "position_embd.weight".into(),
+        }),
+        img_info: None,
+        scale: None,
+        vocab_parallel: None,
+    }
+}
+
+/// 无位置编码的多头注意力，各投影都带偏置
+fn biased_attention(
+    gguf: &GGufModel,
+    iblk: usize,
+    d: usize,
+    nh: usize,
+    causal: bool,
+) -> nn::Attention<String> {
+    ::nn::Attention {
+        nh,
+        nkvh: nh,
+        qkv: linear(gguf, [3 * d, d], &format!("blk.{iblk}.attn_qkv")),
+        q_norm: None,
+        k_norm: None,
+        rope: None,
+        causal,
+        window: None,
+        softcap: None,
+        alibi: None,
+        sinks: None,
+        output: linear(gguf, [d, d], &format!("blk.{iblk}.attn_output")),
+    }
+}
+
+fn gelu_mlp(gguf: &GGufModel, iblk: usize, d: usize, di: usize) -> nn::Mlp<String> {
+    ::nn::Mlp {
+        up: linear(gguf, [di, d], &format!("blk.{iblk}.ffn_up")),
+        act: ::nn::Activation::GeLU,
+        down: linear(gguf, [d, di], &format!("blk.{iblk}.ffn_down")),
+    }
+}
+
+/// 读取 `{name}.weight`，存在 `{name}.bias` 时带偏置
+fn linear(gguf: &GGufModel, shape: [usize; 2], name: &str) -> nn::Linear<String> {
+    let weight = format!("{name}.weight");
+    let bias = format!("{name}.bias");
+    ::nn::Linear::new(
+        gguf.tensors[&*weight].dt(),
+        shape,
+        weight,
+        gguf.tensors.get(&*bias).map(|t| (t.dt(), bias)),
+    )
+}
+
+fn layer_norm(gguf: &GGufModel, d: usize, epsilon: f32, name: &str) -> nn::Normalization<String> {
+    let scale = format!("{name}.weight");
+    let bias = format!("{name}.bias");
+    ::nn::Normalization {
+        d,
+        epsilon: epsilon as _,
+        items: ::nn::NormType::LayerNorm {
+            dt_scale: gguf.tensors[&*scale].dt(),
+            scale,
+            dt_bias: gguf.tensors[&*bias].dt(),
+            bias,
+        },
+    }
+}
+
+/// 从 GGuf 元信息读取 rope 频率缩放方式
+///
+/// 没有记录原始上下文长度时，按 `nctx / factor` 推算。
+fn rope_scaling(gguf: &GGufModel, arch: &str, nctx: usize) -> RopeScaling {
+    let key = |name: &str| format!("{arch}.rope.scaling.{name}");
+    let factor = meta![gguf => (f32) &key("factor"); 1.];
+    let original_nctx = meta![gguf => (usize) &key("original_context_length");
+        (nctx as f32 / factor).round() as usize];
+    match meta![gguf => (str) &key("type"); "none"] {
+        "none" => RopeScaling::None,
+        "linear" => RopeScaling::Linear { factor },
+        "ntk" => RopeScaling::Ntk { factor },
+        "dynamic" => RopeScaling::DynamicNtk {
+            factor,
+            original_nctx,
+        },
+        "yarn" => RopeScaling::Yarn {
+            factor,
+            original_nctx,
+            beta_fast: meta![gguf => (f32) &key("yarn_beta_fast"); 32.],
+            beta_slow: meta![gguf => (f32) &key("yarn_beta_slow"); 1.],
+            attn_factor: meta![gguf => (f32) &key("attn_factor"); 1.],
+        },
+        "llama3" => RopeScaling::Llama3 {
+            factor,
+            original_nctx,
+            low_freq_factor: meta![gguf => (f32) &key("low_freq_factor"); 1.],
+            high_freq_factor: meta![gguf => (f32) &key("high_freq_factor"); 4.],
+        },
+        ty => panic!("unsupported rope scaling type {ty}"),
+    }
+}
+
+/// 将生成的 f32 表转换为模型数据张量
+fn to_data<'a, const N: usize>(tensor: Tensor<Box<[f32]>, N>) -> Tensor<Data<'a>, N> {
+    tensor.map(|data| {
+        let mut blob = Blob::new(size_of_val(&*data));
+        let ([], blob_, []) = (unsafe { blob.align_to_mut::<f32>() }) else {
+            unreachable!()
+        };
+        blob_.copy_from_slice(&data);
+        blob.into()
+    })
 }
@@ -0,0 +1,143 @@
+use crate::{gguf::GGufModel, meta};
+use ggus::{
+    DEFAULT_ALIGNMENT, GGufFileHeader, GGufFileWriter, GGufMetaDataValueType, GGufMetaMapExt,
+};
+use nn::{Distribution, NNGraph, TPTensor};
+use std::{fs::File, io::BufWriter, path::Path};
+
+/// 分片文件记录切分方式的元数据键
+const TP_START: &str = "tensor_parallel.start";
+const TP_LEN: &str = "tensor_parallel.len";
+const TP_TOTAL: &str = "tensor_parallel.total";
+
+/// 把张量并行的一个 rank 的权重写为 GGuf 文件。
+///
+/// 元数据复制自 `gguf` 并记录本 rank 的切分方式，张量按计算图中的切分方式取出。
+/// 运行时生成的张量（如 sin/cos 表）不写入文件，加载时重新生成。
+pub fn write_shard(
+    gguf: &GGufModel,
+    dist: Distribution,
+    graph: &NNGraph<TPTensor<String>>,
+    path: impl AsRef<Path>,
+) -> std::io::Result<()> {
+    assert!(distribution(gguf).is_none(), "cannot shard a shard again");
+    let tensors = graph
+        .shard_weights(|name| gguf.tensors[&**name].as_ref().map(|data| &**data))
+        .into_iter()
+        .filter(|(name, _)| gguf.types.contains_key(&**name))
+        .collect::<Vec<_>>();
+    let tp = [
+        (TP_START, dist.start),
+        (TP_LEN, dist.len),
+        (TP_TOTAL, dist.total),
+    ];
+
+    // 对齐方式也是一个元数据
+    let header = GGufFileHeader::new(
+        3,
+        tensors.len() as _,
+        (gguf.meta_kvs.len() + tp.len() + 1) as _,
+    );
+    let file = BufWriter::new(File::create(path)?);
+    let mut writer = GGufFileWriter::new(file, header)?;
+    writer.write_alignment(DEFAULT_ALIGNMENT)?;
+    for (key, kv) in &gguf.meta_kvs {
+        writer.write_meta_kv(key, kv.ty(), kv.value_bytes())?
+    }
+    for (key, val) in tp {
+        writer.write_meta_kv(key, GGufMetaDataValueType::U64, &(val as u64).to_le_bytes())?
+    }
+
+    let mut writer = writer.finish();
+    for (name, tensor) in tensors {
+        // GGuf 的形状从最内层的维度开始，以数值为单位，张量的最内层维度则是量化块数
+        let ty = gguf.types[&*name];
+        let mut shape = tensor
+            .shape()
+            .iter()
+            .rev()
+            .map(|&d| d as u64)
+            .collect::<Vec<_>>();
+        shape[0] *= ty.size().block_size as u64;
+        writer.write_tensor(&name, ty, &shape, tensor.take())?
+    }
+    writer.finish().map(|_| ())
+}
+
+/// 分片文件记录的切分方式，完整的模型返回 `None`
+pub fn distribution(gguf: &GGufModel) -> Option<Distribution> {
+    gguf.meta_kvs.contains_key(TP_TOTAL).then(|| {
+        let [start, len, total] =
+            [TP_START, TP_LEN, TP_TOTAL].map(|key| meta![gguf => (usize) key]);
+        Distribution::new(start, len, total)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ggus::GGmlType;
+    use nn::{Embedding, Table, TensorMeta, digit_layout::types};
+
+    /// 写一个只有一个张量的 GGuf 文件，`shape` 以数值为单位，从最外层的维度开始
+    fn gguf_file(name: &str, ty: GGmlType, shape: [usize; 2], data: &[u8]) -> Vec<u8> {
+        let mut file = Vec::new();
+        let header = GGufFileHeader::new(3, 1, 1);
+        let mut writer = GGufFileWriter::new(&mut file, header).unwrap();
+        writer.write_alignment(DEFAULT_ALIGNMENT).unwrap();
+        let mut writer = writer.finish();
+        let shape = shape.map(|d| d as u64);
+        writer
+            .write_tensor(name, ty, &[shape[1], shape[0]], data)
+            .unwrap();
+        writer.finish().unwrap();
+        file
+    }
+
+    #[test]
+    fn round_trip_quantized() {
+        const NAME: &str = "token_embd.weight";
+        let ty = GGmlType::Q8_0;
+        let (row, d) = (4, 64);
+        // 每行 2 个量化块
+        let line = d / ty.size().block_size as usize * ty.size().type_size as usize;
+        let data = (0..row * line).map(|i| i as u8).collect::<Vec<_>>();
+        let file = gguf_file(NAME, ty, [row, d], &data);
+        let gguf = GGufModel::read([&*file]);
+
+        let builder = crate::builder();
+        let dir = std::env::temp_dir().join(format!("shard-round-trip-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (i, dist) in Distribution::partition(&[1, 1]).into_iter().enumerate() {
+            let embedding = Embedding {
+                dt: ty.to_digit_layout(),
+                d,
+                wte: Table {
+                    row,
+                    weight: NAME.to_string(),
+                },
+                wpe: None,
+                img_info: None,
+                scale: None,
+                vocab_parallel: None,
+            };
+            let tokens = TensorMeta::new(types::U32, [nn::Dim::from("n")]);
+            let graph = builder
+                .build(embedding.vocab_parallel(dist), [tokens])
+                .unwrap();
+            let path = dir.join(format!("rank{i}.gguf"));
+            write_shard(&gguf, dist, &graph, &path).unwrap();
+
+            let file = std::fs::read(&path).unwrap();
+            let shard = GGufModel::read([&*file]);
+            assert_eq!(distribution(&shard), Some(dist));
+            assert_eq!(shard.types[NAME], ty);
+            let tensor = &shard.tensors[NAME];
+            let rows = dist.range(row);
+            // 张量的最内层维度是量化块数
+            assert_eq!(tensor.shape(), [rows.len(), 2]);
+            assert_eq!(&**tensor.get(), &data[rows.start * line..rows.end * line])
+        }
+        std::fs::remove_dir_all(&dir).unwrap()
+    }
+}